@@ -1,67 +1,346 @@
-use super::user_data::ChatID;
+use super::user_data::{ChatID, MessageID};
 
 use chrono;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use time;
+
+use std::error::Error;
+use std::fmt;
 
 pub trait MessageProcessor {
     fn is_new_message(&mut self, message_id: i64) -> bool;
 
-    fn process_message(&mut self,
-                       message_sender: &mut MessageSender,
-                       chat_id: ChatID,
-                       first_name: &str,
-                       last_name: Option<&str>,
-                       message: &str);
+    /// `Err` explains why the update was ignored, e.g. a button of an outdated menu.
+    fn process_update(&mut self, message_sender: &mut MessageSender, update: IncomingUpdate) -> Result<(), String>;
+
+    /// Called periodically to do the work not triggered by messages.
+    fn process_timer(&mut self, message_sender: &mut MessageSender);
+}
+
+/// Who sent an update, as far as the front-end knows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SenderIdentity {
+    pub first_name: String,
+    pub last_name: Option<String>,
+}
+
+impl SenderIdentity {
+    pub fn new(first_name: &str, last_name: Option<&str>) -> Self {
+        Self {
+            first_name: first_name.to_string(),
+            last_name: last_name.map(str::to_string),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IncomingKind {
+    Text(String),
+    /// `command` has the leading slash, e.g. "/wfh"
+    Command { command: String, arguments: String },
+    /// `key` of the menu sent in `message_id`, `menu_id` is `None` for menus sent without one
+    ButtonPress {
+        message_id: MessageID,
+        menu_id: Option<MenuId>,
+        key: String,
+    },
+}
+
+/// Update from any front-end, dialogs don't see types of the messenger.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncomingUpdate {
+    pub chat_id: ChatID,
+    pub sender: SenderIdentity,
+    pub kind: IncomingKind,
+}
+
+impl IncomingUpdate {
+    /// Text starting with a slash is a command like "/wfh tomorrow".
+    pub fn from_text(chat_id: ChatID, sender: SenderIdentity, text: &str) -> Self {
+        let kind = if text.starts_with('/') {
+            let mut parts = text.splitn(2, char::is_whitespace);
+            IncomingKind::Command {
+                command: parts.next().unwrap_or("").to_string(),
+                arguments: parts.next().unwrap_or("").trim().to_string(),
+            }
+        } else {
+            IncomingKind::Text(text.to_string())
+        };
+        Self { chat_id, sender, kind }
+    }
+
+    pub fn button_press(chat_id: ChatID,
+                        sender: SenderIdentity,
+                        message_id: MessageID,
+                        menu_id: Option<MenuId>,
+                        key: &str)
+                        -> Self {
+        Self {
+            chat_id,
+            sender,
+            kind: IncomingKind::ButtonPress {
+                message_id,
+                menu_id,
+                key: key.to_string(),
+            },
+        }
+    }
 }
 
 pub type Menu = Vec<Vec<String>>;
 
+/// Identifies the dialog which sent a menu, so that buttons of older menus can be rejected.
+pub type MenuId = u64;
+
+const MENU_ID_SEPARATOR: char = ':';
+
+/// Button data like "12:yes", Telegram limits it to 64 bytes.
+pub fn make_callback_data(menu_id: MenuId, key: &str) -> String {
+    format!("{}{}{}", menu_id, MENU_ID_SEPARATOR, key)
+}
+
+/// Menu id and the pressed key, buttons sent before menu ids were introduced have no id.
+pub fn parse_callback_data(data: &str) -> (Option<MenuId>, &str) {
+    match data.find(MENU_ID_SEPARATOR) {
+        Some(position) => {
+            match data[..position].parse::<MenuId>() {
+                Ok(menu_id) => (Some(menu_id), &data[position + 1..]),
+                Err(_) => (None, data),
+            }
+        }
+        None => (None, data),
+    }
+}
+
 pub trait MessageSender {
     fn send_text(&mut self, chat_id: ChatID, text: String);
-    fn send_menu(&mut self, chat_id: ChatID, text: String, menu: Menu);
-    fn send_status_to_channel(&mut self, text: String);
+    fn send_menu(&mut self, chat_id: ChatID, text: String, menu: Menu, menu_id: MenuId);
+    /// Replaces text and menu of a message sent before, no menu is left if `menu` is `None`.
+    fn edit_message(&mut self, chat_id: ChatID, message_id: MessageID, text: String, menu: Option<Menu>, menu_id: MenuId);
+    /// `channel` is "@username" or a numeric chat id, front-ends with one own channel ignore it
+    fn send_status_to_channel(&mut self, channel: &str, text: String);
 }
 
-pub type LocalDate = chrono::Date<chrono::Local>;
+pub type LocalDate = chrono::Date<chrono::FixedOffset>;
+pub type LocalDateTime = chrono::DateTime<chrono::FixedOffset>;
+
+/// Zone in which dates of the user are computed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UserTimeZone {
+    /// Zone of the server, used until the user sets own one
+    Server,
+    Named(Tz),
+}
+
+impl UserTimeZone {
+    /// IANA name like "Europe/Berlin", `None` for the server zone.
+    pub fn get_name(&self) -> Option<&'static str> {
+        match *self {
+            UserTimeZone::Server => None,
+            UserTimeZone::Named(zone) => Some(zone.name()),
+        }
+    }
+
+    pub fn now(&self) -> LocalDateTime {
+        self.from_utc(&Utc::now())
+    }
+
+    pub fn today(&self) -> LocalDate {
+        self.now().date()
+    }
+
+    pub fn from_utc(&self, time: &chrono::DateTime<Utc>) -> LocalDateTime {
+        match *self {
+            UserTimeZone::Server => {
+                let time = time.with_timezone(&chrono::Local);
+                time.with_timezone(time.offset())
+            }
+            UserTimeZone::Named(zone) => {
+                let time = time.with_timezone(&zone);
+                time.with_timezone(&time.offset().fix())
+            }
+        }
+    }
+
+    /// `None` for a time skipped by a daylight saving change, the earlier one of repeated times.
+    pub fn from_local_datetime(&self, time: &NaiveDateTime) -> Option<LocalDateTime> {
+        match *self {
+            UserTimeZone::Server => {
+                let time = chrono::Local.from_local_datetime(time).earliest()?;
+                Some(time.with_timezone(time.offset()))
+            }
+            UserTimeZone::Named(zone) => {
+                let time = zone.from_local_datetime(time).earliest()?;
+                Some(time.with_timezone(&time.offset().fix()))
+            }
+        }
+    }
+
+    pub fn from_local_date(&self, date: &NaiveDate) -> LocalDate {
+        // midday is skipped only together with the whole day, its offset doesn't matter then
+        self.from_local_datetime(&date.and_hms(12, 0, 0))
+            .map_or_else(|| chrono::Date::from_utc(*date, Utc.fix()), |time| time.date())
+    }
+}
 
+/// Dates are parsed when the event is loaded, so a corrupt date fails the loading instead of the bot.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct CalendarDate {
-    date: String,
+    /// Formatted as "%Y-%m-%d"
+    date: NaiveDate,
+    #[serde(rename = "timeZone", default, skip_serializing_if = "Option::is_none")]
+    time_zone: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WholeDayEvent {
+    /// Optional client-side id, makes repeated posting of the same event idempotent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
     summary: String,
     start: CalendarDate,
     end: CalendarDate,
+    /// RRULE lines, e.g. "RRULE:FREQ=WEEKLY;BYDAY=FR"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    recurrence: Vec<String>,
+}
+
+const WEEKDAY_CODES: [(Weekday, &str); 7] = [(Weekday::Mon, "MO"),
+                                             (Weekday::Tue, "TU"),
+                                             (Weekday::Wed, "WE"),
+                                             (Weekday::Thu, "TH"),
+                                             (Weekday::Fri, "FR"),
+                                             (Weekday::Sat, "SA"),
+                                             (Weekday::Sun, "SU")];
+
+/// Value of the RRULE part like "BYDAY=MO,FR".
+fn get_rule_part<'a>(rule: &'a str, name: &str) -> Option<&'a str> {
+    let rule = if rule.starts_with("RRULE:") { &rule["RRULE:".len()..] } else { rule };
+    rule.split(';')
+        .find(|part| part.starts_with(name) && part[name.len()..].starts_with('='))
+        .map(|part| &part[name.len() + 1..])
 }
 
 impl WholeDayEvent {
     pub fn new(text: String, start_date: &LocalDate, end_date: &LocalDate) -> Self {
-        fn format_date(date: &LocalDate) -> String {
-            date.format("%Y-%m-%d").to_string()
-        }
-
-        let start = CalendarDate { date: format_date(start_date) };
-        let end = CalendarDate { date: format_date(end_date) };
+        let start = CalendarDate {
+            date: start_date.naive_local(),
+            time_zone: None,
+        };
+        let end = CalendarDate {
+            date: end_date.naive_local(),
+            time_zone: None,
+        };
 
         Self {
+            id: None,
             summary: text,
             start,
             end,
+            recurrence: Vec::new(),
+        }
+    }
+
+    /// Event repeated every week on the given days, starting from `start_date`.
+    pub fn new_weekly(text: String, start_date: &LocalDate, weekdays: &[Weekday]) -> Self {
+        let days: Vec<&str> = WEEKDAY_CODES.iter()
+            .filter(|&&(weekday, _)| weekdays.contains(&weekday))
+            .map(|&(_, code)| code)
+            .collect();
+        let mut result = Self::new(text, start_date, start_date);
+        result.recurrence = vec![format!("RRULE:FREQ=WEEKLY;BYDAY={}", days.join(","))];
+        result
+    }
+
+    pub fn get_id(&self) -> Option<&String> {
+        self.id.as_ref()
+    }
+
+    pub fn set_id(&mut self, id: String) {
+        self.id = Some(id);
+    }
+
+    pub fn get_summary(&self) -> &String {
+        &self.summary
+    }
+
+    pub fn get_start_date(&self) -> NaiveDate {
+        self.start.date
+    }
+
+    pub fn get_end_date(&self) -> NaiveDate {
+        self.end.date
+    }
+
+    pub fn is_recurring(&self) -> bool {
+        !self.recurrence.is_empty()
+    }
+
+    /// RRULE lines, empty for a single event.
+    pub fn get_recurrence(&self) -> &[String] {
+        &self.recurrence
+    }
+
+    /// Days of weekly recurrence, empty for a single event.
+    pub fn get_weekdays(&self) -> Vec<Weekday> {
+        let days = match self.recurrence.first().and_then(|rule| get_rule_part(rule, "BYDAY")) {
+            Some(days) => days,
+            None => return Vec::new(),
+        };
+        WEEKDAY_CODES.iter()
+            .filter(|&&(_, code)| days.split(',').any(|day| day == code))
+            .map(|&(weekday, _)| weekday)
+            .collect()
+    }
+
+    /// Last day of the recurrence, `None` if it's endless.
+    pub fn get_recurrence_end(&self) -> Option<NaiveDate> {
+        self.recurrence
+            .first()
+            .and_then(|rule| get_rule_part(rule, "UNTIL"))
+            .and_then(|until| NaiveDate::parse_from_str(until, "%Y%m%d").ok())
+    }
+
+    pub fn set_time_zone(&mut self, time_zone: Option<&str>) {
+        self.start.time_zone = time_zone.map(|name| name.to_string());
+        self.end.time_zone = time_zone.map(|name| name.to_string());
+    }
+
+    /// The same event ending on the next day, calendars exclude the end date while the bot includes it.
+    pub fn with_exclusive_end(mut self) -> Self {
+        self.end.date = self.end.date + time::Duration::days(1);
+        self
+    }
+
+    /// Makes the recurrence finish at `last_date`.
+    pub fn stop_recurrence(&mut self, last_date: &NaiveDate) {
+        let until = format!("UNTIL={}", last_date.format("%Y%m%d"));
+        for rule in self.recurrence.iter_mut() {
+            let parts: Vec<String> = rule.split(';')
+                .filter(|part| !part.starts_with("UNTIL="))
+                .map(|part| part.to_string())
+                .chain(Some(until.clone()))
+                .collect();
+            *rule = parts.join(";");
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct CalendarDateTime {
+    /// Formatted as RFC 3339
     #[serde(rename = "dateTime")]
-    date_time: String,
+    date_time: LocalDateTime,
+    #[serde(rename = "timeZone", default, skip_serializing_if = "Option::is_none")]
+    time_zone: Option<String>,
 }
 
-pub type LocalDateTime = chrono::DateTime<chrono::Local>;
-
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PartialDayEvent {
+    /// Optional client-side id, makes repeated posting of the same event idempotent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
     summary: String,
     start: CalendarDateTime,
     end: CalendarDateTime,
@@ -69,22 +348,194 @@ pub struct PartialDayEvent {
 
 impl PartialDayEvent {
     pub fn new(text: String, start_date: &LocalDateTime, end_date: &LocalDateTime) -> Self {
-        fn format_date(date: &LocalDateTime) -> String {
-            date.to_rfc3339()
-        }
-
-        let start = CalendarDateTime { date_time: format_date(start_date) };
-        let end = CalendarDateTime { date_time: format_date(end_date) };
+        let start = CalendarDateTime {
+            date_time: *start_date,
+            time_zone: None,
+        };
+        let end = CalendarDateTime {
+            date_time: *end_date,
+            time_zone: None,
+        };
 
         Self {
+            id: None,
             summary: text,
             start,
             end,
         }
     }
+
+    pub fn get_id(&self) -> Option<&String> {
+        self.id.as_ref()
+    }
+
+    pub fn set_id(&mut self, id: String) {
+        self.id = Some(id);
+    }
+
+    pub fn get_summary(&self) -> &String {
+        &self.summary
+    }
+
+    pub fn set_time_zone(&mut self, time_zone: Option<&str>) {
+        self.start.time_zone = time_zone.map(|name| name.to_string());
+        self.end.time_zone = time_zone.map(|name| name.to_string());
+    }
+
+    pub fn get_start(&self) -> LocalDateTime {
+        self.start.date_time
+    }
+
+    pub fn get_end(&self) -> LocalDateTime {
+        self.end.date_time
+    }
+
+    pub fn get_start_time(&self) -> NaiveDateTime {
+        self.get_start().naive_local()
+    }
+
+    pub fn get_end_time(&self) -> NaiveDateTime {
+        self.get_end().naive_local()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CalendarEvent {
+    WholeDay(WholeDayEvent),
+    PartialDay(PartialDayEvent),
+}
+
+impl CalendarEvent {
+    pub fn get_id(&self) -> Option<&String> {
+        match self {
+            &CalendarEvent::WholeDay(ref event) => event.get_id(),
+            &CalendarEvent::PartialDay(ref event) => event.get_id(),
+        }
+    }
+
+    pub fn set_id(&mut self, id: String) {
+        match self {
+            &mut CalendarEvent::WholeDay(ref mut event) => event.set_id(id),
+            &mut CalendarEvent::PartialDay(ref mut event) => event.set_id(id),
+        }
+    }
+
+    /// Zone of the user, sent to the calendar along with the dates.
+    pub fn set_time_zone(&mut self, time_zone: Option<&str>) {
+        match self {
+            &mut CalendarEvent::WholeDay(ref mut event) => event.set_time_zone(time_zone),
+            &mut CalendarEvent::PartialDay(ref mut event) => event.set_time_zone(time_zone),
+        }
+    }
+
+    pub fn get_summary(&self) -> &String {
+        match self {
+            &CalendarEvent::WholeDay(ref event) => event.get_summary(),
+            &CalendarEvent::PartialDay(ref event) => event.get_summary(),
+        }
+    }
+
+    pub fn get_start_date(&self) -> NaiveDate {
+        match self {
+            &CalendarEvent::WholeDay(ref event) => event.get_start_date(),
+            &CalendarEvent::PartialDay(ref event) => event.get_start_time().date(),
+        }
+    }
+
+    pub fn get_end_date(&self) -> NaiveDate {
+        match self {
+            &CalendarEvent::WholeDay(ref event) => event.get_end_date(),
+            &CalendarEvent::PartialDay(ref event) => event.get_end_time().date(),
+        }
+    }
+
+    /// Last day of the event including recurrence, `None` for endless recurring events.
+    pub fn get_last_date(&self) -> Option<NaiveDate> {
+        match self {
+            &CalendarEvent::WholeDay(ref event) if event.is_recurring() => event.get_recurrence_end(),
+            event => Some(event.get_end_date()),
+        }
+    }
+
+    /// Whether the event takes place on any day between `start` and `end` inclusive.
+    pub fn occurs_between(&self, start: &NaiveDate, end: &NaiveDate) -> bool {
+        let first = self.get_start_date();
+        let last = self.get_last_date();
+        if first > *end || last.map_or(false, |last| last < *start) {
+            return false;
+        }
+
+        match self {
+            &CalendarEvent::WholeDay(ref event) if event.is_recurring() => {
+                let weekdays = event.get_weekdays();
+                let mut date = if first > *start { first } else { *start };
+                // a week is enough to meet every weekday
+                for _ in 0..7 {
+                    if date > *end || last.map_or(false, |last| date > last) {
+                        return false;
+                    }
+                    if weekdays.contains(&date.weekday()) {
+                        return true;
+                    }
+                    date = date + time::Duration::days(1);
+                }
+                false
+            }
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EventsError {
+    Authorization(String),
+    Connection(String),
+    /// HTTP status and the message returned by the calendar
+    Rejected(u16, String),
+    InvalidResponse(String),
+}
+
+impl fmt::Display for EventsError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &EventsError::Authorization(ref message) => write!(formatter, "couldn't authorize in calendar ({})", message),
+            &EventsError::Connection(ref message) => write!(formatter, "calendar is unreachable ({})", message),
+            &EventsError::Rejected(status, ref message) => write!(formatter, "calendar rejected the event ({} {})", status, message),
+            &EventsError::InvalidResponse(ref message) => write!(formatter, "unexpected calendar response ({})", message),
+        }
+    }
 }
 
+impl Error for EventsError {
+    fn description(&self) -> &str {
+        match self {
+            &EventsError::Authorization(_) => "calendar authorization failed",
+            &EventsError::Connection(_) => "calendar connection failed",
+            &EventsError::Rejected(_, _) => "calendar rejected the request",
+            &EventsError::InvalidResponse(_) => "unexpected calendar response",
+        }
+    }
+}
+
+impl EventsError {
+    /// Whether the same request may succeed later
+    pub fn is_transient(&self) -> bool {
+        match self {
+            &EventsError::Authorization(_) | &EventsError::Connection(_) => true,
+            &EventsError::Rejected(status, _) => status == 429 || status >= 500,
+            &EventsError::InvalidResponse(_) => false,
+        }
+    }
+}
+
+pub type EventsResult<T> = Result<T, EventsError>;
+
+/// Post methods return id of the created event which is used to update or delete it later.
+/// `team` selects the calendar, `None` is for users without a team.
 pub trait EventsSender {
-    fn post_whole_day(&mut self, event: WholeDayEvent);
-    fn post_partial_day(&mut self, event: PartialDayEvent);
+    fn post_whole_day(&mut self, team: Option<&str>, event: WholeDayEvent) -> EventsResult<String>;
+    fn post_partial_day(&mut self, team: Option<&str>, event: PartialDayEvent) -> EventsResult<String>;
+    fn update_whole_day(&mut self, team: Option<&str>, event_id: &str, event: WholeDayEvent) -> EventsResult<()>;
+    fn update_partial_day(&mut self, team: Option<&str>, event_id: &str, event: PartialDayEvent) -> EventsResult<()>;
+    fn delete_event(&mut self, team: Option<&str>, event_id: &str) -> EventsResult<()>;
 }
@@ -0,0 +1,247 @@
+use super::basic_structures::{MessageSender, MessageProcessor, Menu, MenuId, IncomingUpdate, SenderIdentity};
+use super::user_data::{ChatID, MessageID};
+
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const TIMER_PERIOD_SECONDS: u64 = 30;
+/// The terminal has a single user
+const CHAT_ID: i64 = 1;
+const FIRST_NAME: &str = "Local";
+const LAST_NAME: &str = "User";
+const DATA_FILE_PREFIX: &str = "cli-";
+
+/// The bot's state is kept apart, so that the production users and outbox aren't touched.
+pub fn get_data_file(data_file: &str) -> String {
+    let path = Path::new(data_file);
+    let file_name = path.file_name().map_or("data.json".into(), |name| name.to_string_lossy());
+    path.with_file_name(format!("{}{}", DATA_FILE_PREFIX, file_name)).to_string_lossy().into_owned()
+}
+
+/// Menu printed last, its keys are pressed by typing their numbers.
+struct PrintedMenu {
+    message_id: MessageID,
+    menu_id: MenuId,
+    keys: Vec<String>,
+}
+
+struct CliMessageSender<W: Write> {
+    output: W,
+    last_message_id: i64,
+    menu: Option<PrintedMenu>,
+}
+
+impl<W: Write> CliMessageSender<W> {
+    fn new(output: W) -> Self {
+        Self {
+            output,
+            last_message_id: 0,
+            menu: None,
+        }
+    }
+
+    fn print(&mut self, text: &str) {
+        if let Err(error) = writeln!(self.output, "{}", text) {
+            warn!("Couldn't print message: {}", error);
+        }
+    }
+
+    fn print_menu(&mut self, message_id: MessageID, menu: Menu, menu_id: MenuId) {
+        let keys: Vec<String> = menu.into_iter().flat_map(|row| row.into_iter()).collect();
+        for (index, key) in keys.iter().enumerate() {
+            self.print(&format!("  {}. {}", index + 1, key));
+        }
+        self.menu = Some(PrintedMenu {
+                             message_id,
+                             menu_id,
+                             keys,
+                         });
+    }
+
+    fn next_message_id(&mut self) -> MessageID {
+        self.last_message_id += 1;
+        MessageID::new(self.last_message_id)
+    }
+
+    /// A number of a key of the last menu presses it, anything else is sent as text.
+    fn to_update(&self, input: &str) -> IncomingUpdate {
+        let chat_id = ChatID::new(CHAT_ID);
+        let sender = SenderIdentity::new(FIRST_NAME, Some(LAST_NAME));
+        let input = input.trim();
+        let pressed = self.menu.as_ref().and_then(|menu| {
+            input.parse::<usize>()
+                .ok()
+                .and_then(|number| number.checked_sub(1))
+                .and_then(|index| menu.keys.get(index))
+                .map(|key| (menu, key))
+        });
+        match pressed {
+            Some((menu, key)) => IncomingUpdate::button_press(chat_id, sender, menu.message_id, Some(menu.menu_id), key),
+            None => IncomingUpdate::from_text(chat_id, sender, input),
+        }
+    }
+}
+
+impl<W: Write> MessageSender for CliMessageSender<W> {
+    fn send_text(&mut self, _chat_id: ChatID, text: String) {
+        self.next_message_id();
+        self.print(&text);
+    }
+
+    fn send_menu(&mut self, _chat_id: ChatID, text: String, menu: Menu, menu_id: MenuId) {
+        let message_id = self.next_message_id();
+        self.print(&text);
+        self.print_menu(message_id, menu, menu_id);
+    }
+
+    fn edit_message(&mut self, _chat_id: ChatID, message_id: MessageID, text: String, menu: Option<Menu>, menu_id: MenuId) {
+        self.print(&format!("(edited) {}", text));
+        match menu {
+            Some(menu) => self.print_menu(message_id, menu, menu_id),
+            None => {
+                if self.menu.as_ref().map_or(false, |menu| menu.message_id == message_id) {
+                    self.menu = None;
+                }
+            }
+        }
+    }
+
+    fn send_status_to_channel(&mut self, channel: &str, text: String) {
+        self.print(&format!("[{}] {}", channel, text));
+    }
+}
+
+/// Talks to the only user in the terminal, for trying dialogs without a bot.
+pub struct CliApi {
+    message_sender: CliMessageSender<io::Stdout>,
+}
+
+impl CliApi {
+    pub fn new() -> Self {
+        Self { message_sender: CliMessageSender::new(io::stdout()) }
+    }
+
+    /// Returns at the end of the input.
+    pub fn process_messages(&mut self, message_processor: &mut MessageProcessor) {
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            let stdin = io::stdin();
+            for line in stdin.lock().lines() {
+                match line {
+                    Ok(line) => {
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(error) => {
+                        warn!("Couldn't read input: {}", error);
+                        break;
+                    }
+                }
+            }
+        });
+        self.message_sender.print("Send /help to see the commands, type a number to press a menu button");
+
+        let timer_period = Duration::from_secs(TIMER_PERIOD_SECONDS);
+        let mut last_timer = Instant::now();
+        loop {
+            match receiver.recv_timeout(timer_period) {
+                Ok(ref line) if line.trim().is_empty() => {}
+                Ok(line) => {
+                    let update = self.message_sender.to_update(&line);
+                    if let Err(error) = message_processor.process_update(&mut self.message_sender, update) {
+                        self.message_sender.print(&error);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if last_timer.elapsed() >= timer_period {
+                message_processor.process_timer(&mut self.message_sender);
+                last_timer = Instant::now();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use basic_structures::{IncomingKind, WholeDayEvent, CalendarEvent};
+    use events_sender::LoggingEventsSender;
+    use message_processor::{UserCollection, init_dialog_types};
+    use outbox::{Outbox, PendingEvent};
+    use save_load_state::{DataSaver, FileDataSaver, UserCollectionSerializationData};
+
+    use chrono::{FixedOffset, TimeZone, Utc};
+
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_menu_input() {
+        let mut message_sender = CliMessageSender::new(Vec::new());
+        let chat_id = ChatID::new(CHAT_ID);
+        let menu = vec![vec!["today".to_string(), "tomorrow".to_string()], vec!["other".to_string()]];
+        message_sender.send_text(chat_id, "Hello".to_string());
+        message_sender.send_menu(chat_id, "When?".to_string(), menu, 3);
+        message_sender.send_status_to_channel("@BigBrotherEvents", "Local User: wfh".to_string());
+        assert_eq!(String::from_utf8(message_sender.output.clone()).unwrap(),
+                   "Hello\nWhen?\n  1. today\n  2. tomorrow\n  3. other\n[@BigBrotherEvents] Local User: wfh\n");
+
+        assert_eq!(message_sender.to_update(" 2 ").kind,
+                   IncomingKind::ButtonPress {
+                       message_id: MessageID::new(2),
+                       menu_id: Some(3),
+                       key: "tomorrow".to_string(),
+                   });
+        assert_eq!(message_sender.to_update("4").kind, IncomingKind::Text("4".to_string()));
+        assert_eq!(message_sender.to_update("0").kind, IncomingKind::Text("0".to_string()));
+        assert_eq!(message_sender.to_update("/wfh tomorrow").kind,
+                   IncomingKind::Command {
+                       command: "/wfh".to_string(),
+                       arguments: "tomorrow".to_string(),
+                   });
+
+        message_sender.edit_message(chat_id, MessageID::new(2), "Posted wfh for tomorrow".to_string(), None, 3);
+        assert_eq!(message_sender.to_update("1").kind, IncomingKind::Text("1".to_string()));
+    }
+
+    #[test]
+    fn test_data_file() {
+        assert_eq!(get_data_file("data.json"), "cli-data.json");
+        assert_eq!(get_data_file("/var/lib/big_brother/data.json"), "/var/lib/big_brother/cli-data.json");
+
+        init_dialog_types();
+        let data_file = env::temp_dir().join(format!("big_brother_test_{}.json", Utc::now().timestamp_subsec_nanos()));
+        let data_file = data_file.to_str().unwrap();
+        let zone = FixedOffset::east(0);
+        let event = WholeDayEvent::new("WFH: J.Doe".to_string(), &zone.ymd(2030, 12, 24), &zone.ymd(2030, 12, 24));
+        let mut outbox = Outbox::new();
+        outbox.push(PendingEvent::new(ChatID::new(42), "wfh".to_string(), None, CalendarEvent::WholeDay(event), None, 0));
+        FileDataSaver::new(data_file).save_data(UserCollectionSerializationData::new(1, Vec::new(), outbox, None)).unwrap();
+        let production_data = fs::read_to_string(data_file).unwrap();
+
+        let cli_data_file = get_data_file(data_file);
+        {
+            let mut events_sender = LoggingEventsSender::new();
+            let mut data_saver = FileDataSaver::new(&cli_data_file);
+            let mut message_processor = UserCollection::new(&mut events_sender, &mut data_saver);
+            let mut message_sender = CliMessageSender::new(Vec::new());
+            let update = message_sender.to_update("/whoami");
+            message_processor.process_update(&mut message_sender, update).unwrap();
+            message_processor.process_timer(&mut message_sender);
+        }
+
+        // the pending event of the production outbox isn't posted to the logging calendar
+        assert_eq!(fs::read_to_string(data_file).unwrap(), production_data);
+        assert!(!fs::read_to_string(&cli_data_file).unwrap().contains("WFH: J.Doe"));
+        fs::remove_file(data_file).unwrap();
+        fs::remove_file(&cli_data_file).unwrap();
+    }
+}
@@ -1,46 +1,700 @@
 use serde_json;
 
+use std::env;
+use std::error::Error;
+use std::fmt;
 use std::fs::OpenOptions;
+use std::io;
 use std::io::BufReader;
+use std::net::SocketAddr;
 
 static CONGIGURATION_FILE: &'static str = "configuration.json";
-static BOT_TOKEN: &'static str = "305992740:AAFLC-zkocg7inSmaaIIydeFW6Gs6aBu2Go";
 static DATA_FILE: &'static str = "data.json";
+static SLACK_API_URL: &'static str = "https://slack.com/api";
+
+/// Kind of absence reported with its own command, e.g. "/vacation".
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AbsenceKind {
+    pub command: String,
+    /// Used in dialog questions and channel messages, e.g. "vacation"
+    pub name: String,
+    /// Calendar event summary is "<calendar_prefix>: <calendar name>"
+    pub calendar_prefix: String,
+}
+
+impl AbsenceKind {
+    fn new(command: &str, name: &str, calendar_prefix: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            name: name.to_string(),
+            calendar_prefix: calendar_prefix.to_string(),
+        }
+    }
+
+    pub fn wfh() -> Self {
+        Self::new("/wfh", "wfh", "WFH")
+    }
+}
+
+fn default_absence_kinds() -> Vec<AbsenceKind> {
+    vec![AbsenceKind::wfh(),
+         AbsenceKind::new("/vacation", "vacation", "Vacation"),
+         AbsenceKind::new("/sick", "sick day", "Sick"),
+         AbsenceKind::new("/trip", "business trip", "Business trip"),
+         AbsenceKind::new("/dayoff", "day off", "Day off")]
+}
+
+/// Hours of the day used as bounds of partial day events, e.g. for "from now".
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkingHours {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl Default for WorkingHours {
+    fn default() -> Self {
+        Self { start: 9, end: 20 }
+    }
+}
+
+impl WorkingHours {
+    fn validate(&self) -> Result<(), String> {
+        if self.start < self.end && self.end < 24 {
+            Ok(())
+        } else {
+            Err(format!("invalid working hours {}-{}", self.start, self.end))
+        }
+    }
+}
+
+/// Kind of calendar events are posted to.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum CalendarBackend {
+    /// Google Calendar with `calendar_id` and `key_file`
+    Google,
+    /// Existing calendar collection like "http://localhost:5232/user/absences/"
+    CalDav {
+        url: String,
+        username: Option<String>,
+        password: Option<String>,
+    },
+    /// Events are written to a file served to calendar clients
+    Ics { file: String },
+}
+
+impl Default for CalendarBackend {
+    fn default() -> Self {
+        CalendarBackend::Google
+    }
+}
+
+impl CalendarBackend {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            &CalendarBackend::Google => Ok(()),
+            &CalendarBackend::CalDav { ref url, .. } => {
+                if url.starts_with("http://") || url.starts_with("https://") {
+                    Ok(())
+                } else {
+                    Err(format!("invalid CalDAV url \"{}\"", url))
+                }
+            }
+            &CalendarBackend::Ics { ref file } => {
+                if file.is_empty() {
+                    Err("ICS file is empty".to_string())
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+/// Calendar to post events to and service account key used to access it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CalendarSettings {
+    pub backend: CalendarBackend,
+    pub calendar_id: String,
+    pub key_file: String,
+}
+
+/// Group of users with own settings, not specified settings are taken from `Configuration`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Team {
+    pub name: String,
+    #[serde(default)]
+    pub calendar_id: Option<String>,
+    #[serde(default)]
+    pub key_file: Option<String>,
+    #[serde(default)]
+    pub calendar_backend: Option<CalendarBackend>,
+    #[serde(default)]
+    pub working_hours: Option<WorkingHours>,
+    #[serde(default)]
+    pub status_channel: Option<String>,
+}
+
+/// Channel is either "@username" or a numeric chat id like "-1001234567890".
+fn validate_channel(channel: &str) -> Result<(), String> {
+    if (channel.starts_with('@') && channel.len() > 1) || channel.parse::<i64>().is_ok() {
+        Ok(())
+    } else {
+        Err(format!("invalid status channel \"{}\"", channel))
+    }
+}
+
+fn default_query_days() -> u32 {
+    7
+}
+
+fn default_dialog_timeout_minutes() -> u32 {
+    60
+}
+
+/// Telegram pushes updates to the embedded HTTP server instead of being polled.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebhookSettings {
+    /// Public HTTPS URL registered in Telegram, not registered if not specified
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Address the HTTP server listens on, e.g. "0.0.0.0:8443"
+    pub listen_address: String,
+    /// Expected in the "X-Telegram-Bot-Api-Secret-Token" header of each update
+    #[serde(default)]
+    pub secret_token: String,
+}
+
+impl WebhookSettings {
+    fn validate(&self) -> Result<(), String> {
+        if self.listen_address.parse::<SocketAddr>().is_err() {
+            return Err(format!("invalid webhook listen_address \"{}\"", self.listen_address));
+        }
+        if self.url.as_ref().map_or(false, |url| !url.starts_with("https://")) {
+            return Err("webhook url should start with https://".to_string());
+        }
+        let valid_character = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+        if self.secret_token.is_empty() || self.secret_token.len() > 256 ||
+           !self.secret_token.chars().all(valid_character) {
+            return Err("webhook secret_token should be 1-256 characters A-Z, a-z, 0-9, _ and -".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Slack app used instead of the Telegram bot.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SlackSettings {
+    /// Bot token like "xoxb-..."
+    #[serde(default)]
+    pub bot_token: String,
+    /// Checks that requests are sent by Slack
+    #[serde(default)]
+    pub signing_secret: String,
+    /// Address the HTTP server receiving slash commands, button presses and events listens on
+    pub listen_address: String,
+    /// Where all channel messages are posted, e.g. "#absences" or "C0123456789"
+    pub status_channel: String,
+    #[serde(default = "default_slack_api_url")]
+    pub api_url: String,
+}
+
+impl SlackSettings {
+    fn validate(&self) -> Result<(), String> {
+        if self.bot_token.is_empty() {
+            return Err("slack bot_token is empty".to_string());
+        }
+        if self.signing_secret.is_empty() {
+            return Err("slack signing_secret is empty".to_string());
+        }
+        if self.listen_address.parse::<SocketAddr>().is_err() {
+            return Err(format!("invalid slack listen_address \"{}\"", self.listen_address));
+        }
+        if self.status_channel.is_empty() {
+            return Err("slack status_channel is empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+fn default_slack_api_url() -> String {
+    SLACK_API_URL.to_string()
+}
+
+fn default_data_file() -> String {
+    DATA_FILE.to_string()
+}
+
+/// Environment variables overriding values from the configuration file.
+static BOT_TOKEN_VARIABLE: &'static str = "BIG_BROTHER_BOT_TOKEN";
+static DATA_FILE_VARIABLE: &'static str = "BIG_BROTHER_DATA_FILE";
+static CALENDAR_ID_VARIABLE: &'static str = "BIG_BROTHER_CALENDAR_ID";
+static KEY_FILE_VARIABLE: &'static str = "BIG_BROTHER_KEY_FILE";
+static STATUS_CHANNEL_VARIABLE: &'static str = "BIG_BROTHER_STATUS_CHANNEL";
+static WEBHOOK_SECRET_VARIABLE: &'static str = "BIG_BROTHER_WEBHOOK_SECRET";
+static SLACK_TOKEN_VARIABLE: &'static str = "BIG_BROTHER_SLACK_TOKEN";
+static SLACK_SECRET_VARIABLE: &'static str = "BIG_BROTHER_SLACK_SIGNING_SECRET";
+static CALDAV_PASSWORD_VARIABLE: &'static str = "BIG_BROTHER_CALDAV_PASSWORD";
+/// Used when the configuration file isn't passed in the command line
+static CONFIGURATION_FILE_VARIABLE: &'static str = "BIG_BROTHER_CONFIG";
+
+#[derive(Debug)]
+pub enum ConfigurationError {
+    /// Unknown command line argument or a missing argument value
+    Arguments(String),
+    Read(String, io::Error),
+    Parse(String, serde_json::Error),
+    /// Required setting and the environment variable which can provide it
+    Missing(&'static str, &'static str),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigurationError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &ConfigurationError::Arguments(ref message) => write!(formatter, "{}", message),
+            &ConfigurationError::Read(ref path, ref error) => write!(formatter, "couldn't read {}: {}", path, error),
+            &ConfigurationError::Parse(ref path, ref error) => write!(formatter, "couldn't parse {}: {}", path, error),
+            &ConfigurationError::Missing(name, variable) => {
+                write!(formatter, "{} is not specified, set it in the configuration file or in {}", name, variable)
+            }
+            &ConfigurationError::Invalid(ref message) => write!(formatter, "{}", message),
+        }
+    }
+}
+
+impl Error for ConfigurationError {
+    fn description(&self) -> &str {
+        match self {
+            &ConfigurationError::Arguments(_) => "invalid command line arguments",
+            &ConfigurationError::Read(_, _) => "couldn't read configuration file",
+            &ConfigurationError::Parse(_, _) => "couldn't parse configuration file",
+            &ConfigurationError::Missing(_, _) => "required setting is missing",
+            &ConfigurationError::Invalid(_) => "invalid configuration",
+        }
+    }
+}
+
+/// Command line arguments.
+#[derive(Debug, Default, PartialEq)]
+pub struct Arguments {
+    /// From `--config <path>`
+    pub config_path: Option<String>,
+    /// `--cli` talks to the user in the terminal and only logs calendar events
+    pub cli: bool,
+}
+
+pub fn parse_arguments<I>(arguments: I) -> Result<Arguments, ConfigurationError>
+    where I: IntoIterator<Item = String>
+{
+    let mut result = Arguments::default();
+    let mut arguments = arguments.into_iter();
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "-c" | "--config" => {
+                let value = arguments.next()
+                    .ok_or_else(|| ConfigurationError::Arguments(format!("{} requires a file path", argument)))?;
+                result.config_path = Some(value);
+            }
+            "--cli" => result.cli = true,
+            _ => return Err(ConfigurationError::Arguments(format!("unknown argument \"{}\"", argument))),
+        }
+    }
+    Ok(result)
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct Configuration {
+    #[serde(default)]
     pub bot_token: String,
+    #[serde(default = "default_data_file")]
     pub data_file: String,
+    #[serde(default = "default_absence_kinds")]
+    pub absence_kinds: Vec<AbsenceKind>,
+    /// Required for the Google backend
+    #[serde(default)]
+    pub calendar_id: String,
+    /// Service account key of the Google backend
+    #[serde(default)]
+    pub key_file: String,
+    #[serde(default)]
+    pub calendar_backend: CalendarBackend,
+    #[serde(default)]
+    pub working_hours: WorkingHours,
+    /// Where channel messages of users without a team are posted, required for Telegram
+    #[serde(default)]
+    pub status_channel: String,
+    /// Hour of the day when the daily digest is posted, no digest if not specified
+    #[serde(default)]
+    pub digest_hour: Option<u32>,
+    /// Days starting from today covered by /whereis without dates
+    #[serde(default = "default_query_days")]
+    pub query_days: u32,
+    /// Hour after which users who haven't reported are reminded, no reminders if not specified
+    #[serde(default)]
+    pub reminder_hour: Option<u32>,
+    /// Unfinished dialogs are dropped after this idle time
+    #[serde(default = "default_dialog_timeout_minutes")]
+    pub dialog_timeout_minutes: u32,
+    /// Updates are received with long polling if not specified
+    #[serde(default)]
+    pub webhook: Option<WebhookSettings>,
+    /// Slack is used instead of Telegram if specified
+    #[serde(default)]
+    pub slack: Option<SlackSettings>,
+    #[serde(default)]
+    pub teams: Vec<Team>,
 }
 
 impl Configuration {
-    pub fn load() -> Configuration {
-        let file = OpenOptions::new().read(true).open(CONGIGURATION_FILE);
-        match file {
-            Ok(opened_file) => {
-                let reader = BufReader::new(opened_file);
-                let configuration: Result<Configuration, _> = serde_json::from_reader(reader);
-                match configuration {
-                    Ok(conf) => conf,
-                    Err(err) => {
-                        error!("Couldn't deserealize config: {:?}", err);
+    /// Reads the file passed in `arguments` (or the default one if it exists),
+    /// then applies environment variables and validates the result.
+    /// No bot token is required in the terminal mode.
+    pub fn load(arguments: &Arguments) -> Result<Configuration, ConfigurationError> {
+        let path = match arguments.config_path {
+            Some(ref path) => Some(path.clone()),
+            None => env::var(CONFIGURATION_FILE_VARIABLE).ok(),
+        };
+        let mut configuration = match path {
+            Some(path) => Self::read(&path)?,
+            None => {
+                match Self::read(CONGIGURATION_FILE) {
+                    Err(ConfigurationError::Read(_, ref error)) if error.kind() == io::ErrorKind::NotFound => {
+                        info!("No {}, using environment variables only", CONGIGURATION_FILE);
                         Default::default()
                     }
+                    result => result?,
                 }
             }
-            Err(err) => {
-                warn!("Coludn't open configuration file {:?}", err);
-                Default::default()
+        };
+
+        configuration.apply_overrides(|name| env::var(name).ok());
+        if !arguments.cli {
+            configuration.check_required()?;
+        }
+        configuration.validate().map_err(ConfigurationError::Invalid)?;
+        Ok(configuration)
+    }
+
+    fn read(path: &str) -> Result<Configuration, ConfigurationError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .open(path)
+            .map_err(|error| ConfigurationError::Read(path.to_string(), error))?;
+        serde_json::from_reader(BufReader::new(file))
+            .map_err(|error| ConfigurationError::Parse(path.to_string(), error))
+    }
+
+    fn apply_overrides<F>(&mut self, get_variable: F)
+        where F: Fn(&str) -> Option<String>
+    {
+        if let Some(value) = get_variable(BOT_TOKEN_VARIABLE) {
+            self.bot_token = value;
+        }
+        if let Some(value) = get_variable(DATA_FILE_VARIABLE) {
+            self.data_file = value;
+        }
+        if let Some(value) = get_variable(CALENDAR_ID_VARIABLE) {
+            self.calendar_id = value;
+        }
+        if let Some(value) = get_variable(KEY_FILE_VARIABLE) {
+            self.key_file = value;
+        }
+        if let Some(value) = get_variable(STATUS_CHANNEL_VARIABLE) {
+            self.status_channel = value;
+        }
+        if let (Some(value), Some(webhook)) = (get_variable(WEBHOOK_SECRET_VARIABLE), self.webhook.as_mut()) {
+            webhook.secret_token = value;
+        }
+        if let Some(slack) = self.slack.as_mut() {
+            if let Some(value) = get_variable(SLACK_TOKEN_VARIABLE) {
+                slack.bot_token = value;
+            }
+            if let Some(value) = get_variable(SLACK_SECRET_VARIABLE) {
+                slack.signing_secret = value;
+            }
+        }
+        if let Some(value) = get_variable(CALDAV_PASSWORD_VARIABLE) {
+            // the same password for the default calendar and calendars of teams
+            let team_backends = self.teams.iter_mut().filter_map(|team| team.calendar_backend.as_mut());
+            for backend in Some(&mut self.calendar_backend).into_iter().chain(team_backends) {
+                if let &mut CalendarBackend::CalDav { ref mut password, .. } = backend {
+                    *password = Some(value.clone());
+                }
+            }
+        }
+    }
+
+    fn check_required(&self) -> Result<(), ConfigurationError> {
+        if self.slack.is_none() && self.bot_token.trim().is_empty() {
+            return Err(ConfigurationError::Missing("bot_token", BOT_TOKEN_VARIABLE));
+        }
+        if self.slack.is_none() && self.status_channel.is_empty() {
+            return Err(ConfigurationError::Missing("status_channel", STATUS_CHANNEL_VARIABLE));
+        }
+
+        // only calendars which are actually used need the Google settings
+        let teams = self.teams.iter().map(Some);
+        for settings in Some(None).into_iter().chain(teams).map(|team| self.get_calendar(team)) {
+            if settings.backend != CalendarBackend::Google {
+                continue;
+            }
+            if settings.calendar_id.is_empty() {
+                return Err(ConfigurationError::Missing("calendar_id", CALENDAR_ID_VARIABLE));
+            }
+            if settings.key_file.is_empty() {
+                return Err(ConfigurationError::Missing("key_file", KEY_FILE_VARIABLE));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Configuration {
+    /// Checks consistency of the settings, calendar keys are checked by the events sender.
+    pub fn validate(&self) -> Result<(), String> {
+        self.calendar_backend.validate()?;
+        self.working_hours.validate()?;
+        if !self.status_channel.is_empty() {
+            validate_channel(&self.status_channel)?;
+        }
+        if self.digest_hour.map_or(false, |hour| hour >= 24) {
+            return Err(format!("invalid digest_hour {}", self.digest_hour.unwrap()));
+        }
+        if self.reminder_hour.map_or(false, |hour| hour >= 24) {
+            return Err(format!("invalid reminder_hour {}", self.reminder_hour.unwrap()));
+        }
+        if self.query_days == 0 {
+            return Err("query_days should be positive".to_string());
+        }
+        if self.dialog_timeout_minutes == 0 {
+            return Err("dialog_timeout_minutes should be positive".to_string());
+        }
+        if let Some(ref webhook) = self.webhook {
+            webhook.validate()?;
+        }
+        if let Some(ref slack) = self.slack {
+            slack.validate()?;
+        }
+
+        for (index, team) in self.teams.iter().enumerate() {
+            if team.name.is_empty() {
+                return Err(format!("team #{} has no name", index + 1));
+            }
+            if self.teams[..index].iter().any(|other| other.name == team.name) {
+                return Err(format!("team \"{}\" is specified twice", team.name));
+            }
+            let is_google = self.get_calendar(Some(team)).backend == CalendarBackend::Google;
+            if is_google && team.calendar_id.as_ref().map_or(false, |id| id.is_empty()) {
+                return Err(format!("calendar_id of team \"{}\" is empty", team.name));
+            }
+            if let Some(ref backend) = team.calendar_backend {
+                backend.validate().map_err(|error| format!("{} of team \"{}\"", error, team.name))?;
+            }
+            if let Some(ref working_hours) = team.working_hours {
+                working_hours.validate()
+                    .map_err(|error| format!("{} of team \"{}\"", error, team.name))?;
+            }
+            if let Some(ref channel) = team.status_channel {
+                validate_channel(channel).map_err(|error| format!("{} of team \"{}\"", error, team.name))?;
             }
         }
+
+        Ok(())
+    }
+
+    pub fn get_calendar(&self, team: Option<&Team>) -> CalendarSettings {
+        CalendarSettings {
+            backend: team.and_then(|team| team.calendar_backend.clone())
+                .unwrap_or_else(|| self.calendar_backend.clone()),
+            calendar_id: team.and_then(|team| team.calendar_id.clone())
+                .unwrap_or_else(|| self.calendar_id.clone()),
+            key_file: team.and_then(|team| team.key_file.clone())
+                .unwrap_or_else(|| self.key_file.clone()),
+        }
     }
 }
 
 impl Default for Configuration {
     fn default() -> Self {
         Self {
-            bot_token: BOT_TOKEN.to_string(),
-            data_file: DATA_FILE.to_string(),
+            bot_token: String::new(),
+            data_file: default_data_file(),
+            absence_kinds: default_absence_kinds(),
+            calendar_id: String::new(),
+            key_file: String::new(),
+            calendar_backend: CalendarBackend::default(),
+            working_hours: WorkingHours::default(),
+            status_channel: String::new(),
+            digest_hour: None,
+            query_days: default_query_days(),
+            reminder_hour: None,
+            dialog_timeout_minutes: default_dialog_timeout_minutes(),
+            webhook: None,
+            slack: None,
+            teams: Vec::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arguments(arguments: &[&str]) -> Vec<String> {
+        arguments.iter().map(|argument| argument.to_string()).collect()
+    }
+
+    #[test]
+    fn test_arguments() {
+        let config_path = |path: &str| {
+            Arguments {
+                config_path: Some(path.to_string()),
+                cli: false,
+            }
+        };
+        assert_eq!(parse_arguments(arguments(&[])).unwrap(), Arguments::default());
+        assert_eq!(parse_arguments(arguments(&["--config", "team.json"])).unwrap(),
+                   config_path("team.json"));
+        assert_eq!(parse_arguments(arguments(&["-c", "team.json"])).unwrap(),
+                   config_path("team.json"));
+        assert_eq!(parse_arguments(arguments(&["--cli", "-c", "team.json"])).unwrap(),
+                   Arguments { cli: true, ..config_path("team.json") });
+        assert!(parse_arguments(arguments(&["--config"])).is_err());
+        assert!(parse_arguments(arguments(&["--token", "secret"])).is_err());
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut configuration: Configuration =
+            serde_json::from_str(r#"{"bot_token": "from file", "data_file": "file.json"}"#).unwrap();
+        configuration.apply_overrides(|name| if name == BOT_TOKEN_VARIABLE {
+                                          Some("from env".to_string())
+                                      } else {
+                                          None
+                                      });
+
+        assert_eq!(configuration.bot_token, "from env");
+        assert_eq!(configuration.data_file, "file.json");
+        assert_eq!(configuration.calendar_id, "");
+    }
+
+    #[test]
+    fn test_missing_token() {
+        let configuration: Configuration = serde_json::from_str("{}").unwrap();
+        match configuration.check_required() {
+            Err(ConfigurationError::Missing("bot_token", variable)) => assert_eq!(variable, BOT_TOKEN_VARIABLE),
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        let configuration: Configuration = serde_json::from_str(r##"{"slack": {"listen_address": "127.0.0.1:3000",
+                                                                              "status_channel": "#absences"},
+                                                                    "calendar_backend": {"type": "ics",
+                                                                                         "file": "absences.ics"}}"##)
+            .unwrap();
+        assert!(configuration.check_required().is_ok());
+        assert_eq!(configuration.slack.as_ref().unwrap().api_url, SLACK_API_URL);
+        assert_eq!(configuration.validate(), Err("slack bot_token is empty".to_string()));
+    }
+
+    #[test]
+    fn test_missing_calendar() {
+        let mut configuration: Configuration =
+            serde_json::from_str(r#"{"bot_token": "token", "status_channel": "@absences", "key_file": "key.json"}"#)
+                .unwrap();
+        match configuration.check_required() {
+            Err(ConfigurationError::Missing("calendar_id", variable)) => assert_eq!(variable, CALENDAR_ID_VARIABLE),
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        configuration.apply_overrides(|name| if name == CALENDAR_ID_VARIABLE {
+                                          Some("absences@group.calendar.google.com".to_string())
+                                      } else {
+                                          None
+                                      });
+        assert!(configuration.check_required().is_ok());
+
+        // a team posting to Google needs an id as well
+        configuration.calendar_id = String::new();
+        configuration.calendar_backend = CalendarBackend::Ics { file: "absences.ics".to_string() };
+        assert!(configuration.check_required().is_ok());
+        configuration.teams = serde_json::from_str(r#"[{"name": "support", "calendar_backend": {"type": "google"}}]"#)
+            .unwrap();
+        assert!(configuration.check_required().is_err());
+        configuration.teams[0].calendar_id = Some("support@group.calendar.google.com".to_string());
+        assert!(configuration.check_required().is_ok());
+    }
+
+    #[test]
+    fn test_webhook() {
+        let mut configuration: Configuration =
+            serde_json::from_str(r#"{"webhook": {"listen_address": "127.0.0.1:8443"}}"#).unwrap();
+        assert!(configuration.validate().is_err());
+
+        configuration.apply_overrides(|name| if name == WEBHOOK_SECRET_VARIABLE {
+                                          Some("from-env_1".to_string())
+                                      } else {
+                                          None
+                                      });
+        assert_eq!(configuration.webhook.as_ref().unwrap().secret_token, "from-env_1");
+        assert!(configuration.validate().is_ok());
+
+        configuration.webhook.as_mut().unwrap().secret_token = "not secret!".to_string();
+        assert!(configuration.validate().is_err());
+        configuration.webhook.as_mut().unwrap().secret_token = "secret".to_string();
+        configuration.webhook.as_mut().unwrap().listen_address = "localhost".to_string();
+        assert!(configuration.validate().is_err());
+    }
+
+    #[test]
+    fn test_calendar_backend() {
+        let configuration: Configuration = serde_json::from_str(r#"{
+            "calendar_backend": {"type": "ics", "file": "absences.ics"},
+            "teams": [{"name": "support",
+                       "calendar_backend": {"type": "caldav", "url": "http://localhost:5232/support/absences/",
+                                            "username": "support"}}]
+        }"#)
+            .unwrap();
+        assert!(configuration.validate().is_ok());
+        assert_eq!(configuration.get_calendar(None).backend,
+                   CalendarBackend::Ics { file: "absences.ics".to_string() });
+        assert_eq!(configuration.get_calendar(configuration.teams.first()).backend,
+                   CalendarBackend::CalDav {
+                       url: "http://localhost:5232/support/absences/".to_string(),
+                       username: Some("support".to_string()),
+                       password: None,
+                   });
+    }
+
+    #[test]
+    fn test_caldav_password() {
+        let mut configuration: Configuration = serde_json::from_str(r#"{
+            "calendar_backend": {"type": "caldav", "url": "http://localhost:5232/user/absences/", "username": "user"},
+            "teams": [{"name": "support", "calendar_id": "",
+                       "calendar_backend": {"type": "caldav", "url": "http://localhost:5232/support/absences/",
+                                            "username": "support", "password": "from file"}},
+                      {"name": "sales", "calendar_backend": {"type": "ics", "file": "sales.ics"}}]
+        }"#)
+            .unwrap();
+        // teams not posting to Google don't need a calendar id
+        assert!(configuration.validate().is_ok());
+
+        configuration.apply_overrides(|name| if name == CALDAV_PASSWORD_VARIABLE {
+                                          Some("from env".to_string())
+                                      } else {
+                                          None
+                                      });
+        let password = |team: Option<&Team>| match configuration.get_calendar(team).backend {
+            CalendarBackend::CalDav { password, .. } => password,
+            other => panic!("Unexpected backend {:?}", other),
+        };
+        assert_eq!(password(None), Some("from env".to_string()));
+        assert_eq!(password(configuration.teams.first()), Some("from env".to_string()));
+        assert_eq!(configuration.get_calendar(configuration.teams.last()).backend,
+                   CalendarBackend::Ics { file: "sales.ics".to_string() });
+
+        configuration.teams[1].calendar_backend = Some(CalendarBackend::Google);
+        configuration.teams[1].calendar_id = Some(String::new());
+        assert_eq!(configuration.validate(), Err("calendar_id of team \"sales\" is empty".to_string()));
+    }
+}
@@ -0,0 +1,189 @@
+use basic_structures::{EventsSender, EventsError, EventsResult, WholeDayEvent, PartialDayEvent, CalendarEvent};
+use events_sender::{icalendar, check_response, make_https_client};
+
+use chrono::Utc;
+
+use hyper;
+use hyper::header::{Authorization, Basic, ContentType, IfNoneMatch};
+use hyper::method::Method;
+use hyper::mime::{Mime, TopLevel, SubLevel, Attr, Value};
+
+/// Stores each event as "<collection url><event id>.ics" with PUT requests.
+pub struct CalDavCalendar {
+    /// With the trailing slash
+    url: String,
+    credentials: Option<Basic>,
+    http_client: hyper::Client,
+}
+
+impl CalDavCalendar {
+    pub fn new(url: &str, username: Option<&String>, password: Option<&String>) -> Self {
+        let url = if url.ends_with('/') { url.to_string() } else { format!("{}/", url) };
+        Self {
+            url,
+            credentials: username.map(|username| {
+                                          Basic {
+                                              username: username.clone(),
+                                              password: password.cloned(),
+                                          }
+                                      }),
+            http_client: make_https_client(),
+        }
+    }
+
+    fn request<'a>(&'a self, method: Method, event_id: &str) -> hyper::client::RequestBuilder<'a> {
+        let request = self.http_client.request(method, &format!("{}{}.ics", self.url, event_id));
+        match self.credentials {
+            Some(ref credentials) => request.header(Authorization(credentials.clone())),
+            None => request,
+        }
+    }
+
+    /// New events are created only if there is no event with this id yet.
+    fn put_event(&mut self, event_id: Option<&String>, event: CalendarEvent, is_new: bool) -> EventsResult<String> {
+        let now = Utc::now();
+        let event_id = event_id.cloned().unwrap_or_else(|| icalendar::make_uid(&now));
+        let calendar = icalendar::make_calendar(icalendar::make_event(&event_id, &event, &now));
+        debug!("Putting {}", event_id);
+
+        let mut request = self.request(Method::Put, &event_id)
+            .header(ContentType(Mime(TopLevel::Text, SubLevel::Ext("calendar".into()), vec![(Attr::Charset, Value::Utf8)])))
+            .body(calendar.as_str());
+        if is_new {
+            request = request.header(IfNoneMatch::Any);
+        }
+        match check_response(request.send()) {
+            // Event with this id is already posted by one of the previous attempts
+            Err(EventsError::Rejected(412, _)) if is_new => Ok(event_id),
+            response => response.map(|_| event_id),
+        }
+    }
+}
+
+impl EventsSender for CalDavCalendar {
+    fn post_whole_day(&mut self, _: Option<&str>, event: WholeDayEvent) -> EventsResult<String> {
+        let event_id = event.get_id().cloned();
+        self.put_event(event_id.as_ref(), CalendarEvent::WholeDay(event), true)
+    }
+
+    fn post_partial_day(&mut self, _: Option<&str>, event: PartialDayEvent) -> EventsResult<String> {
+        let event_id = event.get_id().cloned();
+        self.put_event(event_id.as_ref(), CalendarEvent::PartialDay(event), true)
+    }
+
+    fn update_whole_day(&mut self, _: Option<&str>, event_id: &str, event: WholeDayEvent) -> EventsResult<()> {
+        self.put_event(Some(&event_id.to_string()), CalendarEvent::WholeDay(event), false).map(|_| ())
+    }
+
+    fn update_partial_day(&mut self, _: Option<&str>, event_id: &str, event: PartialDayEvent) -> EventsResult<()> {
+        self.put_event(Some(&event_id.to_string()), CalendarEvent::PartialDay(event), false).map(|_| ())
+    }
+
+    fn delete_event(&mut self, _: Option<&str>, event_id: &str) -> EventsResult<()> {
+        debug!("Deleting {}", event_id);
+        match check_response(self.request(Method::Delete, event_id).send()) {
+            // Event is already deleted, e.g. by one of the previous attempts
+            Ok(_) | Err(EventsError::Rejected(404, _)) | Err(EventsError::Rejected(410, _)) => Ok(()),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::{FixedOffset, TimeZone};
+    use hyper::server::{Request, Response, Server};
+    use hyper::status::StatusCode;
+    use hyper::uri::RequestUri;
+
+    use std::env;
+    use std::io::Read;
+    use std::sync::{Arc, Mutex};
+
+    fn make_event(summary: &str) -> WholeDayEvent {
+        let zone = FixedOffset::east(0);
+        let mut event = WholeDayEvent::new(summary.to_string(), &zone.ymd(2030, 12, 24), &zone.ymd(2030, 12, 24));
+        event.set_id(format!("bb{}", Utc::now().timestamp_subsec_nanos()));
+        event
+    }
+
+    fn check_calendar(calendar: &mut CalDavCalendar) -> String {
+        let event = make_event("WFH: J.Doe");
+        let event_id = calendar.post_whole_day(None, event.clone()).unwrap();
+        assert_eq!(Some(&event_id), event.get_id());
+        assert_eq!(calendar.post_whole_day(None, event).unwrap(), event_id);
+        calendar.update_whole_day(None, &event_id, make_event("Sick: J.Doe")).unwrap();
+        calendar.delete_event(None, &event_id).unwrap();
+        // already deleted
+        calendar.delete_event(None, &event_id).unwrap();
+        event_id
+    }
+
+    #[test]
+    fn test_caldav() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let mock = move |mut request: Request, mut response: Response| {
+            let path = match request.uri {
+                RequestUri::AbsolutePath(ref path) => path.clone(),
+                _ => String::new(),
+            };
+            let is_new = request.headers.get::<IfNoneMatch>().is_some();
+            let is_authorized = request.headers.get::<Authorization<Basic>>().map_or(false, |credentials| {
+                credentials.username == "user" && credentials.password == Some("secret".to_string())
+            });
+            let mut body = String::new();
+            request.read_to_string(&mut body).unwrap();
+
+            let mut requests = recorded.lock().unwrap();
+            let is_sent = |sent_method: Method| {
+                requests.iter().any(|&(ref method, ref other_path, _)| *method == sent_method && *other_path == path)
+            };
+            *response.status_mut() = match request.method {
+                _ if !is_authorized => StatusCode::Unauthorized,
+                Method::Put if is_new && is_sent(Method::Put) => StatusCode::PreconditionFailed,
+                Method::Put => StatusCode::Created,
+                _ if is_sent(Method::Delete) => StatusCode::NotFound,
+                _ => StatusCode::NoContent,
+            };
+            requests.push((request.method.clone(), path, body));
+            response.send(b"").unwrap();
+        };
+        let mut server = Server::http("127.0.0.1:0").unwrap().handle(mock).unwrap();
+
+        let url = format!("http://{}/user/absences", server.socket);
+        let event_id = check_calendar(&mut CalDavCalendar::new(&url,
+                                                               Some(&"user".to_string()),
+                                                               Some(&"secret".to_string())));
+        let mut anonymous = CalDavCalendar::new(&url, None, None);
+        match anonymous.delete_event(None, &event_id) {
+            Err(EventsError::Rejected(401, _)) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+        server.close().unwrap();
+
+        let requests = requests.lock().unwrap();
+        let path = format!("/user/absences/{}.ics", event_id);
+        assert_eq!(requests.iter().map(|&(ref method, ref request_path, _)| (method.clone(), request_path == &path)).collect::<Vec<_>>(),
+                   vec![(Method::Put, true),
+                        (Method::Put, true),
+                        (Method::Put, true),
+                        (Method::Delete, true),
+                        (Method::Delete, true),
+                        (Method::Delete, true)]);
+        assert!(requests[2].2.contains("SUMMARY:Sick: J.Doe\r\n"));
+    }
+
+    /// Needs a running Radicale with an existing calendar collection, e.g.
+    /// `BIG_BROTHER_TEST_CALDAV_URL=http://localhost:5232/user/absences/ cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn test_radicale() {
+        let url = env::var("BIG_BROTHER_TEST_CALDAV_URL").unwrap_or("http://localhost:5232/user/absences/".to_string());
+        let username = env::var("BIG_BROTHER_TEST_CALDAV_USER").ok();
+        let password = env::var("BIG_BROTHER_TEST_CALDAV_PASSWORD").ok();
+        check_calendar(&mut CalDavCalendar::new(&url, username.as_ref(), password.as_ref()));
+    }
+}
@@ -0,0 +1,187 @@
+use basic_structures::CalendarEvent;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use time;
+
+const LINE_END: &str = "\r\n";
+/// Longer lines are folded
+const MAX_LINE_LENGTH: usize = 75;
+const PRODUCT_ID: &str = "-//BigBrother//Absences//EN";
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n")
+}
+
+fn format_date(date: &NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+fn format_time(time: &DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Continuation lines start with a space, multi-byte characters aren't split.
+fn fold_line(line: &str) -> String {
+    let mut result = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            result.push_str(LINE_END);
+            result.push(' ');
+            length = 1;
+        }
+        result.push(c);
+        length += c.len_utf8();
+    }
+    result
+}
+
+/// Unique id for events posted without a client-side id.
+pub fn make_uid(now: &DateTime<Utc>) -> String {
+    format!("bb{}", now.format("%Y%m%d%H%M%S%f"))
+}
+
+/// VEVENT lines, whole day events end on the next day in iCalendar.
+pub fn make_event(uid: &str, event: &CalendarEvent, now: &DateTime<Utc>) -> Vec<String> {
+    let mut lines = vec!["BEGIN:VEVENT".to_string(),
+                         format!("UID:{}", uid),
+                         format!("DTSTAMP:{}", format_time(now)),
+                         format!("SUMMARY:{}", escape_text(event.get_summary()))];
+    match event {
+        &CalendarEvent::WholeDay(ref event) => {
+            lines.push(format!("DTSTART;VALUE=DATE:{}", format_date(&event.get_start_date())));
+            lines.push(format!("DTEND;VALUE=DATE:{}",
+                               format_date(&(event.get_end_date() + time::Duration::days(1)))));
+            lines.extend(event.get_recurrence().iter().cloned());
+        }
+        &CalendarEvent::PartialDay(ref event) => {
+            lines.push(format!("DTSTART:{}", format_time(&event.get_start().with_timezone(&Utc))));
+            lines.push(format!("DTEND:{}", format_time(&event.get_end().with_timezone(&Utc))));
+        }
+    }
+    lines.push("END:VEVENT".to_string());
+    lines
+}
+
+/// Calendar object with the lines of its events.
+pub fn make_calendar<I>(event_lines: I) -> String
+    where I: IntoIterator<Item = String>
+{
+    let mut lines = vec!["BEGIN:VCALENDAR".to_string(), "VERSION:2.0".to_string(), format!("PRODID:{}", PRODUCT_ID)];
+    lines.extend(event_lines);
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold_line(line) + LINE_END).collect()
+}
+
+/// Content of a calendar file, the bot owns only the events.
+pub struct ParsedCalendar {
+    /// Other properties and components like X-WR-CALNAME and VTIMEZONE, without the calendar's own lines
+    pub other_lines: Vec<String>,
+    /// Lines of each VEVENT with its UID
+    pub events: Vec<(String, Vec<String>)>,
+}
+
+/// Written by `make_calendar` itself
+fn is_calendar_line(line: &str) -> bool {
+    line == "BEGIN:VCALENDAR" || line == "END:VCALENDAR" || line.starts_with("VERSION:") ||
+    line.starts_with("PRODID:")
+}
+
+pub fn parse_calendar(calendar: &str) -> ParsedCalendar {
+    let mut result = ParsedCalendar {
+        other_lines: Vec::new(),
+        events: Vec::new(),
+    };
+    let mut current: Option<Vec<String>> = None;
+    // folded lines are joined back, so that long UIDs are found
+    let mut lines: Vec<String> = Vec::new();
+    for line in calendar.lines() {
+        match lines.last_mut() {
+            Some(last) if line.starts_with(' ') => last.push_str(&line[1..]),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    // nested components like VTIMEZONE are kept as they are
+    let mut depth = 0;
+    for line in lines {
+        if line == "BEGIN:VEVENT" {
+            current = Some(vec![line]);
+        } else if let Some(mut event) = current.take() {
+            let is_end = line == "END:VEVENT";
+            event.push(line);
+            if is_end {
+                let uid = event.iter()
+                    .find(|line| line.starts_with("UID:"))
+                    .map(|line| line["UID:".len()..].to_string())
+                    .unwrap_or_default();
+                result.events.push((uid, event));
+            } else {
+                current = Some(event);
+            }
+        } else if !line.is_empty() && !(depth == 0 && is_calendar_line(&line)) {
+            if line.starts_with("BEGIN:") {
+                depth += 1;
+            } else if line.starts_with("END:") && depth > 0 {
+                depth -= 1;
+            }
+            result.other_lines.push(line);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use basic_structures::{WholeDayEvent, PartialDayEvent};
+
+    use chrono::{FixedOffset, TimeZone, Weekday};
+
+    #[test]
+    fn test_calendar() {
+        let zone = FixedOffset::east(3 * 3600);
+        let now = Utc.ymd(2030, 12, 20).and_hms(10, 0, 0);
+        let whole_day = WholeDayEvent::new_weekly("WFH: J.Doe".to_string(), &zone.ymd(2030, 12, 20), &[Weekday::Fri]);
+        let partial_day = PartialDayEvent::new("Day off: J.Doe; afternoon".to_string(),
+                                               &zone.ymd(2030, 12, 24).and_hms(14, 0, 0),
+                                               &zone.ymd(2030, 12, 24).and_hms(20, 0, 0));
+        let long_uid = "x".repeat(80);
+        let mut lines = make_event("event1", &CalendarEvent::WholeDay(whole_day), &now);
+        lines.extend(make_event(&long_uid, &CalendarEvent::PartialDay(partial_day), &now));
+        let calendar = make_calendar(lines);
+
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(calendar.contains("\r\nDTSTART;VALUE=DATE:20301220\r\nDTEND;VALUE=DATE:20301221\r\n\
+                                   RRULE:FREQ=WEEKLY;BYDAY=FR\r\n"));
+        assert!(calendar.contains("\r\nSUMMARY:Day off: J.Doe\\; afternoon\r\n\
+                                   DTSTART:20301224T110000Z\r\nDTEND:20301224T170000Z\r\n"));
+        assert!(calendar.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+
+        let parsed = parse_calendar(&calendar);
+        assert_eq!(parsed.events.iter().map(|&(ref uid, _)| uid.clone()).collect::<Vec<_>>(),
+                   vec!["event1".to_string(), long_uid]);
+        assert_eq!(parsed.events[0].1.len(), 8);
+        assert!(parsed.other_lines.is_empty());
+    }
+
+    #[test]
+    fn test_other_lines() {
+        let calendar = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Other//EN\r\nX-WR-CALNAME:Absences\r\n\
+                        BEGIN:VTIMEZONE\r\nTZID:Europe/Berlin\r\nBEGIN:STANDARD\r\nTZOFFSETTO:+0100\r\n\
+                        END:STANDARD\r\nEND:VTIMEZONE\r\n\
+                        BEGIN:VEVENT\r\nUID:event1\r\nSUMMARY:WFH\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let parsed = parse_calendar(calendar);
+        assert_eq!(parsed.other_lines,
+                   vec!["X-WR-CALNAME:Absences", "BEGIN:VTIMEZONE", "TZID:Europe/Berlin", "BEGIN:STANDARD",
+                        "TZOFFSETTO:+0100", "END:STANDARD", "END:VTIMEZONE"]);
+        assert_eq!(parsed.events.len(), 1);
+
+        // written back, the content isn't duplicated
+        let written = make_calendar(parsed.other_lines.into_iter().chain(parsed.events[0].1.clone()));
+        let parsed = parse_calendar(&written);
+        assert_eq!(parsed.other_lines.len(), 7);
+        assert_eq!(written.matches("VERSION:").count(), 1);
+    }
+}
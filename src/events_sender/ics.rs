@@ -0,0 +1,130 @@
+use basic_structures::{EventsSender, EventsError, EventsResult, WholeDayEvent, PartialDayEvent, CalendarEvent};
+use events_sender::icalendar;
+
+use chrono::Utc;
+
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+
+/// Keeps events in an iCalendar file, the whole file is rewritten on each change.
+/// Other content of the file, e.g. time zones and the calendar's name, is kept.
+pub struct IcsCalendar {
+    file: String,
+}
+
+fn to_error(error: io::Error) -> EventsError {
+    EventsError::Connection(error.to_string())
+}
+
+impl IcsCalendar {
+    pub fn new(file: &str) -> Self {
+        Self { file: file.to_string() }
+    }
+
+    fn read_calendar(&self) -> EventsResult<icalendar::ParsedCalendar> {
+        let mut content = String::new();
+        match fs::File::open(&self.file) {
+            Ok(mut file) => {
+                file.read_to_string(&mut content).map_err(to_error)?;
+            }
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(to_error(error)),
+        }
+        Ok(icalendar::parse_calendar(&content))
+    }
+
+    /// Readers never see a partially written file.
+    fn write_calendar(&self, calendar: icalendar::ParsedCalendar) -> EventsResult<()> {
+        let temporary_file = format!("{}.tmp", self.file);
+        let events = calendar.events.into_iter().flat_map(|(_, lines)| lines.into_iter());
+        let calendar = icalendar::make_calendar(calendar.other_lines.into_iter().chain(events));
+        fs::File::create(&temporary_file)
+            .and_then(|mut file| file.write_all(calendar.as_bytes()))
+            .and_then(|_| fs::rename(&temporary_file, &self.file))
+            .map_err(to_error)
+    }
+
+    /// Replaces the event with the same id, so repeated posting doesn't duplicate it.
+    fn put_event(&mut self, event_id: Option<&String>, event: CalendarEvent) -> EventsResult<String> {
+        let now = Utc::now();
+        let event_id = event_id.cloned().unwrap_or_else(|| icalendar::make_uid(&now));
+        let lines = icalendar::make_event(&event_id, &event, &now);
+
+        let mut calendar = self.read_calendar()?;
+        match calendar.events.iter().position(|&(ref uid, _)| *uid == event_id) {
+            Some(index) => calendar.events[index].1 = lines,
+            None => calendar.events.push((event_id.clone(), lines)),
+        }
+        self.write_calendar(calendar)?;
+        Ok(event_id)
+    }
+}
+
+impl EventsSender for IcsCalendar {
+    fn post_whole_day(&mut self, _: Option<&str>, event: WholeDayEvent) -> EventsResult<String> {
+        let event_id = event.get_id().cloned();
+        self.put_event(event_id.as_ref(), CalendarEvent::WholeDay(event))
+    }
+
+    fn post_partial_day(&mut self, _: Option<&str>, event: PartialDayEvent) -> EventsResult<String> {
+        let event_id = event.get_id().cloned();
+        self.put_event(event_id.as_ref(), CalendarEvent::PartialDay(event))
+    }
+
+    fn update_whole_day(&mut self, _: Option<&str>, event_id: &str, event: WholeDayEvent) -> EventsResult<()> {
+        self.put_event(Some(&event_id.to_string()), CalendarEvent::WholeDay(event)).map(|_| ())
+    }
+
+    fn update_partial_day(&mut self, _: Option<&str>, event_id: &str, event: PartialDayEvent) -> EventsResult<()> {
+        self.put_event(Some(&event_id.to_string()), CalendarEvent::PartialDay(event)).map(|_| ())
+    }
+
+    fn delete_event(&mut self, _: Option<&str>, event_id: &str) -> EventsResult<()> {
+        let mut calendar = self.read_calendar()?;
+        calendar.events.retain(|&(ref uid, _)| uid != event_id);
+        self.write_calendar(calendar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::{FixedOffset, TimeZone};
+
+    use std::env;
+
+    #[test]
+    fn test_ics_file() {
+        let path = env::temp_dir().join(format!("big_brother_test_{}.ics", Utc::now().timestamp_subsec_nanos()));
+        // written by a calendar client
+        fs::File::create(&path)
+            .unwrap()
+            .write_all(b"BEGIN:VCALENDAR\r\nVERSION:2.0\r\nX-WR-CALNAME:Absences\r\nEND:VCALENDAR\r\n")
+            .unwrap();
+        let mut calendar = IcsCalendar::new(path.to_str().unwrap());
+        let zone = FixedOffset::east(0);
+        let event = |summary: &str| WholeDayEvent::new(summary.to_string(), &zone.ymd(2030, 12, 24), &zone.ymd(2030, 12, 24));
+
+        let mut first = event("WFH: J.Doe");
+        first.set_id("first".to_string());
+        assert_eq!(calendar.post_whole_day(None, first.clone()).unwrap(), "first");
+        assert_eq!(calendar.post_whole_day(None, first).unwrap(), "first");
+        let second = calendar.post_whole_day(None, event("Vacation: J.Smith")).unwrap();
+        calendar.update_whole_day(None, "first", event("Sick: J.Doe")).unwrap();
+
+        let mut content = String::new();
+        fs::File::open(&path).unwrap().read_to_string(&mut content).unwrap();
+        let parsed = icalendar::parse_calendar(&content);
+        assert_eq!(parsed.other_lines, vec!["X-WR-CALNAME:Absences"]);
+        let events = parsed.events;
+        assert_eq!(events.iter().map(|&(ref uid, _)| uid.as_str()).collect::<Vec<_>>(),
+                   vec!["first", second.as_str()]);
+        assert!(events[0].1.contains(&"SUMMARY:Sick: J.Doe".to_string()));
+
+        calendar.delete_event(None, "first").unwrap();
+        assert_eq!(calendar.read_calendar().unwrap().events.len(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+}
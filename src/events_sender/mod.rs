@@ -0,0 +1,310 @@
+use super::basic_structures::{EventsSender, EventsError, EventsResult, WholeDayEvent, PartialDayEvent};
+use super::configuration::{Configuration, CalendarSettings, CalendarBackend};
+
+use hyper;
+use hyper_rustls;
+use yup_oauth2;
+use yup_oauth2::GetToken;
+use serde::Serialize;
+use serde_json;
+
+use std::collections::HashMap;
+use std::io::Read;
+
+mod icalendar;
+mod caldav;
+mod ics;
+
+use self::caldav::CalDavCalendar;
+use self::ics::IcsCalendar;
+
+fn make_https_client() -> hyper::Client {
+    let ssl = hyper_rustls::TlsClient::new();
+    let connector = hyper::net::HttpsConnector::new(ssl);
+    hyper::Client::with_connector(connector)
+}
+
+struct GoogleCalendar {
+    accessor: yup_oauth2::ServiceAccountAccess<hyper::Client>,
+    events_url: String,
+    http_client: hyper::Client,
+}
+
+impl GoogleCalendar {
+    fn new(settings: &CalendarSettings) -> Result<Self, String> {
+        let secret = yup_oauth2::service_account_key_from_file(&settings.key_file)
+            .map_err(|error| format!("couldn't read calendar key {}: {}", settings.key_file, error))?;
+        let accessor = yup_oauth2::ServiceAccountAccess::new(secret, make_https_client());
+        let events_url = format!("https://www.googleapis.com/calendar/v3/calendars/{}/events",
+                                 settings.calendar_id.replace("@", "%40"));
+
+        Ok(Self {
+               accessor,
+               events_url,
+               http_client: make_https_client(),
+           })
+    }
+
+    fn get_token(&mut self) -> EventsResult<String> {
+        self.accessor
+            .token(&["https://www.googleapis.com/auth/calendar"])
+            .map(|token| token.access_token)
+            .map_err(|error| EventsError::Authorization(error.to_string()))
+    }
+
+    fn post_event<T>(&mut self, event_id: Option<&String>, event: &T) -> EventsResult<String>
+        where T: Serialize
+    {
+        let token = self.get_token()?;
+        let event_string = serde_json::to_string(&event).unwrap();
+        debug!("Sending {}", event_string);
+        let res = self.http_client.post(&format!("{}?alt=json", self.events_url))
+                  .header(hyper::header::ContentType::json())
+                  .header(hyper::header::Authorization(hyper::header::Bearer{token}))
+                  .body(event_string.as_str()).send();
+
+        match (check_response(res), event_id) {
+            // Event with this id is already posted by one of the previous attempts
+            (Err(EventsError::Rejected(409, _)), Some(event_id)) => Ok(event_id.clone()),
+            (response, _) => {
+                serde_json::from_reader::<_, InsertedEvent>(response?)
+                    .map(|inserted| inserted.id)
+                    .map_err(|error| EventsError::InvalidResponse(error.to_string()))
+            }
+        }
+    }
+
+    /// Replaces the whole event, patching would merge the old start and end into the new ones,
+    /// e.g. a whole day event moved to hours would keep its "date".
+    fn update_event<T>(&mut self, event_id: &str, event: &T) -> EventsResult<()>
+        where T: Serialize
+    {
+        let token = self.get_token()?;
+        let event_string = serde_json::to_string(&event).unwrap();
+        debug!("Updating {} with {}", event_id, event_string);
+        let res = self.http_client.put(&format!("{}/{}?alt=json", self.events_url, event_id))
+                  .header(hyper::header::ContentType::json())
+                  .header(hyper::header::Authorization(hyper::header::Bearer{token}))
+                  .body(event_string.as_str()).send();
+
+        check_response(res).map(|_| ())
+    }
+}
+
+impl EventsSender for GoogleCalendar {
+    fn post_whole_day(&mut self, _: Option<&str>, event: WholeDayEvent) -> EventsResult<String> {
+        let event = event.with_exclusive_end();
+        self.post_event(event.get_id(), &event)
+    }
+
+    fn post_partial_day(&mut self, _: Option<&str>, event: PartialDayEvent) -> EventsResult<String> {
+        self.post_event(event.get_id(), &event)
+    }
+
+    fn update_whole_day(&mut self, _: Option<&str>, event_id: &str, event: WholeDayEvent) -> EventsResult<()> {
+        self.update_event(event_id, &event.with_exclusive_end())
+    }
+
+    fn update_partial_day(&mut self, _: Option<&str>, event_id: &str, event: PartialDayEvent) -> EventsResult<()> {
+        self.update_event(event_id, &event)
+    }
+
+    fn delete_event(&mut self, _: Option<&str>, event_id: &str) -> EventsResult<()> {
+        let token = self.get_token()?;
+        debug!("Deleting {}", event_id);
+        let res = self.http_client.delete(&format!("{}/{}", self.events_url, event_id))
+                  .header(hyper::header::Authorization(hyper::header::Bearer{token}))
+                  .send();
+
+        check_response(res).map(|_| ())
+    }
+}
+
+fn make_calendar(settings: &CalendarSettings) -> Result<Box<EventsSender>, String> {
+    Ok(match settings.backend {
+           CalendarBackend::Google => Box::new(GoogleCalendar::new(settings)?),
+           CalendarBackend::CalDav { ref url, ref username, ref password } => {
+               Box::new(CalDavCalendar::new(url, username.as_ref(), password.as_ref()))
+           }
+           CalendarBackend::Ics { ref file } => Box::new(IcsCalendar::new(file)),
+       })
+}
+
+/// Posts events to the calendar of the user's team.
+pub struct CalendarEventsSender {
+    default_calendar: Box<EventsSender>,
+    /// Only for teams with own calendar settings
+    team_calendars: HashMap<String, Box<EventsSender>>,
+}
+
+impl CalendarEventsSender {
+    pub fn new(configuration: &Configuration) -> Result<Self, String> {
+        let default_settings = configuration.get_calendar(None);
+        let default_calendar = make_calendar(&default_settings)?;
+
+        let mut team_calendars = HashMap::new();
+        for team in configuration.teams.iter() {
+            let settings = configuration.get_calendar(Some(team));
+            if settings != default_settings {
+                let calendar = make_calendar(&settings)
+                    .map_err(|error| format!("{} (team \"{}\")", error, team.name))?;
+                team_calendars.insert(team.name.clone(), calendar);
+            }
+        }
+
+        Ok(Self {
+               default_calendar,
+               team_calendars,
+           })
+    }
+
+    fn get_calendar(&mut self, team: Option<&str>) -> &mut EventsSender {
+        match team {
+            Some(team) if self.team_calendars.contains_key(team) => &mut **self.team_calendars.get_mut(team).unwrap(),
+            _ => &mut *self.default_calendar,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct InsertedEvent {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetails {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ErrorDetails,
+}
+
+fn check_response(response: hyper::Result<hyper::client::Response>) -> EventsResult<hyper::client::Response> {
+    let mut response = response.map_err(|error| EventsError::Connection(error.to_string()))?;
+    if response.status.is_success() {
+        return Ok(response);
+    }
+
+    let mut body = String::new();
+    if let Err(error) = response.read_to_string(&mut body) {
+        warn!("Couldn't read calendar error response: {:?}", error);
+    }
+    let message = serde_json::from_str::<ErrorResponse>(&body)
+        .map(|error_response| error_response.error.message)
+        .unwrap_or(body);
+    error!("Calendar request failed with {}: {}", response.status, message);
+    Err(EventsError::Rejected(response.status.to_u16(), message))
+}
+
+impl EventsSender for CalendarEventsSender {
+    fn post_whole_day(&mut self, team: Option<&str>, event: WholeDayEvent) -> EventsResult<String> {
+        self.get_calendar(team).post_whole_day(team, event)
+    }
+
+    fn post_partial_day(&mut self, team: Option<&str>, event: PartialDayEvent) -> EventsResult<String> {
+        self.get_calendar(team).post_partial_day(team, event)
+    }
+
+    fn update_whole_day(&mut self, team: Option<&str>, event_id: &str, event: WholeDayEvent) -> EventsResult<()> {
+        self.get_calendar(team).update_whole_day(team, event_id, event)
+    }
+
+    fn update_partial_day(&mut self, team: Option<&str>, event_id: &str, event: PartialDayEvent) -> EventsResult<()> {
+        self.get_calendar(team).update_partial_day(team, event_id, event)
+    }
+
+    fn delete_event(&mut self, team: Option<&str>, event_id: &str) -> EventsResult<()> {
+        self.get_calendar(team).delete_event(team, event_id)
+    }
+}
+
+/// Prints events instead of posting them, for trying dialogs without a calendar.
+pub struct LoggingEventsSender {
+    posted_count: usize,
+}
+
+impl LoggingEventsSender {
+    pub fn new() -> Self {
+        Self { posted_count: 0 }
+    }
+
+    fn print<T>(&self, action: &str, team: Option<&str>, event: Option<&T>)
+        where T: Serialize
+    {
+        let calendar = team.map_or("default calendar".to_string(), |team| format!("calendar of {}", team));
+        match event {
+            Some(event) => println!("[{}] {} {}", calendar, action, serde_json::to_string_pretty(event).unwrap()),
+            None => println!("[{}] {}", calendar, action),
+        }
+    }
+
+    fn post<T>(&mut self, team: Option<&str>, event_id: Option<&String>, event: &T) -> EventsResult<String>
+        where T: Serialize
+    {
+        self.posted_count += 1;
+        let event_id = event_id.cloned().unwrap_or_else(|| format!("logged{}", self.posted_count));
+        self.print(&format!("Post {}", event_id), team, Some(event));
+        Ok(event_id)
+    }
+}
+
+impl EventsSender for LoggingEventsSender {
+    fn post_whole_day(&mut self, team: Option<&str>, event: WholeDayEvent) -> EventsResult<String> {
+        self.post(team, event.get_id(), &event)
+    }
+
+    fn post_partial_day(&mut self, team: Option<&str>, event: PartialDayEvent) -> EventsResult<String> {
+        self.post(team, event.get_id(), &event)
+    }
+
+    fn update_whole_day(&mut self, team: Option<&str>, event_id: &str, event: WholeDayEvent) -> EventsResult<()> {
+        self.print(&format!("Update {}", event_id), team, Some(&event));
+        Ok(())
+    }
+
+    fn update_partial_day(&mut self, team: Option<&str>, event_id: &str, event: PartialDayEvent) -> EventsResult<()> {
+        self.print(&format!("Update {}", event_id), team, Some(&event));
+        Ok(())
+    }
+
+    fn delete_event(&mut self, team: Option<&str>, event_id: &str) -> EventsResult<()> {
+        self.print::<WholeDayEvent>(&format!("Delete {}", event_id), team, None);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::{FixedOffset, TimeZone};
+
+    #[test]
+    fn test_google_whole_day_payload() {
+        let zone = FixedOffset::east(3 * 3600);
+        let mut event = WholeDayEvent::new("Vacation: J.Doe".to_string(), &zone.ymd(2030, 12, 24), &zone.ymd(2030, 12, 28));
+        event.set_time_zone(Some("Europe/Moscow"));
+        assert_eq!(serde_json::to_string(&event.clone().with_exclusive_end()).unwrap(),
+                   "{\"summary\":\"Vacation: J.Doe\",\
+                    \"start\":{\"date\":\"2030-12-24\",\"timeZone\":\"Europe/Moscow\"},\
+                    \"end\":{\"date\":\"2030-12-29\",\"timeZone\":\"Europe/Moscow\"}}");
+        // the bot itself keeps the inclusive end
+        assert_eq!(event.get_end_date(), zone.ymd(2030, 12, 28).naive_local());
+    }
+
+    #[test]
+    fn test_google_moved_event_payload() {
+        // a whole day event moved to hours is sent as a complete event without "date"
+        let zone = FixedOffset::east(3 * 3600);
+        let mut event = PartialDayEvent::new("Vacation: J.Doe".to_string(),
+                                             &zone.ymd(2030, 12, 25).and_hms(15, 0, 0),
+                                             &zone.ymd(2030, 12, 25).and_hms(20, 0, 0));
+        event.set_time_zone(Some("Europe/Moscow"));
+        assert_eq!(serde_json::to_string(&event).unwrap(),
+                   "{\"summary\":\"Vacation: J.Doe\",\
+                    \"start\":{\"dateTime\":\"2030-12-25T15:00:00+03:00\",\"timeZone\":\"Europe/Moscow\"},\
+                    \"end\":{\"dateTime\":\"2030-12-25T20:00:00+03:00\",\"timeZone\":\"Europe/Moscow\"}}");
+    }
+}
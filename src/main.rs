@@ -11,18 +11,27 @@ extern crate hyper_rustls;
 extern crate log;
 extern crate env_logger;
 extern crate chrono;
+extern crate chrono_tz;
 extern crate time;
 extern crate yup_oauth2;
 #[macro_use]
 extern crate lazy_static;
+extern crate ring;
+extern crate url;
 
 mod basic_structures;
 mod message_processor;
 mod configuration;
 mod user_data;
 mod save_load_state;
+mod outbox;
 mod events_sender;
 mod telegram_api;
+mod slack_api;
+mod cli_api;
+
+use std::env;
+use std::process;
 
 fn main() {
     env_logger::try_init().unwrap_or_else(|error| {
@@ -33,12 +42,55 @@ fn main() {
                                       });
     message_processor::init_dialog_types();
 
-    let configuration = configuration::Configuration::load();
-    
-    let mut message_sender = telegram_api::TelegramApi::new(&configuration.bot_token);
-    let mut events_sender = events_sender::CalendarEventsSender::new();
-    let mut data_saver = save_load_state::FileDataSaver::new(&configuration.data_file);
-    let mut message_processor = message_processor::UserCollection::new(&mut events_sender,
+    let arguments = configuration::parse_arguments(env::args().skip(1)).unwrap_or_else(|error| {
+        error!("Couldn't parse arguments: {}", error);
+        process::exit(1);
+    });
+    let configuration = configuration::Configuration::load(&arguments).unwrap_or_else(|error| {
+        error!("Couldn't load configuration: {}", error);
+        process::exit(1);
+    });
+    message_processor::init_configuration(&configuration);
+
+    let mut events_sender: Box<basic_structures::EventsSender> = if arguments.cli {
+        Box::new(events_sender::LoggingEventsSender::new())
+    } else {
+        Box::new(events_sender::CalendarEventsSender::new(&configuration).unwrap_or_else(|error| {
+            error!("Couldn't initialize calendar: {}", error);
+            process::exit(1);
+        }))
+    };
+    let data_file = if arguments.cli {
+        cli_api::get_data_file(&configuration.data_file)
+    } else {
+        configuration.data_file.clone()
+    };
+    let mut data_saver = save_load_state::FileDataSaver::new(&data_file);
+    let mut message_processor = message_processor::UserCollection::new(&mut *events_sender,
                                                                        &mut data_saver);
+
+    if arguments.cli {
+        cli_api::CliApi::new().process_messages(&mut message_processor);
+        return;
+    }
+
+    if let Some(ref slack) = configuration.slack {
+        if let Err(error) = slack_api::SlackApi::new(slack).process_messages(&mut message_processor) {
+            error!("Slack front-end failed: {}", error);
+            process::exit(1);
+        }
+        return;
+    }
+
+    let mut message_sender = telegram_api::TelegramApi::new(&configuration.bot_token);
+    if let Err(error) = message_sender.set_commands(&message_processor::get_commands()) {
+        warn!("Couldn't set bot commands: {}", error);
+    }
+    if let Some(ref webhook) = configuration.webhook {
+        message_sender.start_webhook(webhook).unwrap_or_else(|error| {
+            error!("Couldn't start webhook: {}", error);
+            process::exit(1);
+        });
+    }
     message_sender.process_messages(&mut message_processor);
 }
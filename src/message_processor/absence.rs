@@ -0,0 +1,683 @@
+use message_processor::dialog_processing::{YES_NO_MENU, DialogAction, ReplyMessage,
+                                           DialogInitializationResult, DynamicSerializable,
+                                           StaticNameGetter, Dialog, Event, ChannelMessage,
+                                           CommandDescription};
+use basic_structures::{LocalDate, LocalDateTime, UserTimeZone, WholeDayEvent, PartialDayEvent, CalendarEvent, Menu};
+use configuration::{AbsenceKind, WorkingHours};
+use message_processor::date_parsing::{When, parse_when, parse_time, parse_every, next_of_weekdays};
+use message_processor::teams::get_working_hours;
+use user_data::UserInfo;
+
+use chrono::{Timelike, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use time;
+
+use serde_json;
+
+use std::sync::Mutex;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct InitialState {}
+
+const TODAY : &str = "today";
+const TOMORROW : &str = "tomorrow";
+const UNTILL_NOW : &str = "untill now";
+const FROM_NOW : &str = "from now";
+const MORNING : &str = "morning";
+const AFTERNOON : &str = "afternoon";
+const HOURS : &str = "hours";
+const OTHER : &str = "other";
+
+const TIME_MENU_COLUMNS : usize = 4;
+/// Partial day events end on the same day
+const LAST_END_HOUR : u32 = 23;
+
+lazy_static! {
+    pub static ref WHEN_MENU : Menu = vec!(vec!(TODAY.into(), TOMORROW.into()),
+                                           vec!(UNTILL_NOW.into(),(FROM_NOW.into())),
+                                           vec!(MORNING.into(), AFTERNOON.into()),
+                                           vec!(HOURS.into(), OTHER.into()));
+}
+
+lazy_static! {
+    static ref ABSENCE_KINDS: Mutex<Vec<AbsenceKind>> = Mutex::new(Vec::new());
+}
+
+pub fn register_absence_kinds(kinds: Vec<AbsenceKind>) {
+    *ABSENCE_KINDS.lock().unwrap() = kinds;
+}
+
+pub fn get_absence_kinds() -> Vec<AbsenceKind> {
+    ABSENCE_KINDS.lock().unwrap().clone()
+}
+
+fn find_absence_kind(text: &str) -> Option<AbsenceKind> {
+    let command = text.split_whitespace().next().unwrap_or("");
+    ABSENCE_KINDS.lock()
+        .unwrap()
+        .iter()
+        .find(|kind| kind.command == command)
+        .cloned()
+}
+
+impl InitialState {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn try_process(&mut self, text: &str, kind: &AbsenceKind, user_info: &mut UserInfo) -> (AbsenceState, DialogAction) {
+        if text.split_whitespace().next() == Some(kind.command.as_str()) {
+            match user_info.get_calendar_name() {
+                Some(_) => {
+                    let arguments = text.trim()[kind.command.len()..].trim();
+                    if arguments.is_empty() {
+                        (AbsenceState::ChooseMode(ChooseModeStateState::new()),
+                         DialogAction::ProcessAndContinue(Some(ReplyMessage::new("When?", Some(WHEN_MENU.clone()))), None))
+                    } else if let Some(weekdays) = parse_every(arguments) {
+                        confirm_weekly(&weekdays, kind)
+                    } else {
+                        let working_hours = get_working_hours(user_info);
+                        let zone = user_info.get_time_zone();
+                        match parse_when(arguments, &zone.today().naive_local())
+                            .and_then(|when| confirm_when(&when, kind, &working_hours, &zone)) {
+                            Some(result) => result,
+                            None => (AbsenceState::ChooseMode(ChooseModeStateState::new()),
+                                     DialogAction::ProcessAndContinue(Some(ReplyMessage::new(format!("Couldn't recognize \"{}\". When?", arguments),
+                                                                                             Some(WHEN_MENU.clone()))),
+                                                                      None)),
+                        }
+                    }
+                }
+                None => stop_on_missing_name(),
+            }
+        } else {
+            (AbsenceState::Initial(InitialState::new()), DialogAction::Stop)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ChooseModeStateState {}
+
+impl ChooseModeStateState {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn try_process(&mut self, text: &str, kind: &AbsenceKind, user_info: &mut UserInfo) -> (AbsenceState, DialogAction) {
+        let zone = user_info.get_time_zone();
+        let today = zone.today().naive_local();
+        let working_hours = get_working_hours(user_info);
+        let confirm_today = |when: When| {
+            confirm_when(&when, kind, &working_hours, &zone)
+                .unwrap_or((AbsenceState::Initial(InitialState::new()), DialogAction::Stop))
+        };
+        let confirm = |state: ConfirmationState, when: &str| {
+            (AbsenceState::Confirmation(state),
+             DialogAction::ProcessAndContinue(Some(ReplyMessage::new(format!("Confirm event {} for {}?", kind.name, when),
+                                                                     Some(YES_NO_MENU.clone()))),
+                                              None))
+        };
+
+        let now = zone.now();
+
+        match text {
+            TODAY => confirm(ConfirmationState::Today, "today"),
+            TOMORROW => confirm(ConfirmationState::Tomorrow, "tomorrow"),
+            UNTILL_NOW if get_before_now(&working_hours, &now).is_none() => ask_again_on_no_hours(UNTILL_NOW),
+            UNTILL_NOW => confirm(ConfirmationState::TodayBeforeNow, "today before now"),
+            FROM_NOW if get_from_now(&working_hours, &now).is_none() => ask_again_on_no_hours(FROM_NOW),
+            FROM_NOW => confirm(ConfirmationState::TodayFromNow, "today from now"),
+            MORNING => confirm_today(When::Morning(today)),
+            AFTERNOON => confirm_today(When::Afternoon(today)),
+            HOURS => (AbsenceState::ChooseStartTime(ChooseStartTimeState::new()),
+             DialogAction::ProcessAndContinue(Some(ReplyMessage::new("From?", Some(make_start_time_menu(&working_hours)))),
+                                              None)),
+            OTHER => (AbsenceState::ChooseDates(ChooseDatesState::new()),
+             DialogAction::ProcessAndContinue(Some(ReplyMessage::new(DATES_FORMAT_HINT, None)),
+                                              None)),
+            _ => (AbsenceState::Initial(InitialState::new()), DialogAction::Stop)
+        }
+
+    }
+}
+
+fn make_time_menu(first_hour: u32, last_hour: u32) -> Menu {
+    let times: Vec<String> = (first_hour..last_hour + 1).map(|hour| format!("{:02}:00", hour)).collect();
+    times.chunks(TIME_MENU_COLUMNS).map(|row| row.to_vec()).collect()
+}
+
+fn make_start_time_menu(working_hours: &WorkingHours) -> Menu {
+    make_time_menu(working_hours.start, working_hours.end - 1)
+}
+
+/// Hours after `start` till the end of working hours or at least the next hour.
+fn make_end_time_menu(start: &NaiveTime, working_hours: &WorkingHours) -> Menu {
+    let first_hour = start.hour() + 1;
+    make_time_menu(first_hour, working_hours.end.max(first_hour).min(LAST_END_HOUR))
+}
+
+fn stop_on_missing_name() -> (AbsenceState, DialogAction) {
+    (AbsenceState::Initial(InitialState::new()),
+     DialogAction::ProcessAndStop(Some(ReplyMessage::new("Please specify your calendar name using /setmyname", None)),
+                                  None,
+                                  None))
+}
+
+/// The working day hasn't started yet or is already over.
+fn ask_again_on_no_hours(text: &str) -> (AbsenceState, DialogAction) {
+    (AbsenceState::ChooseMode(ChooseModeStateState::new()),
+     DialogAction::ProcessAndContinue(Some(ReplyMessage::new(format!("No working hours are left for \"{}\". When?", text),
+                                                             Some(WHEN_MENU.clone()))),
+                                      None))
+}
+
+fn stop_on_unrecognized_time(text: &str) -> (AbsenceState, DialogAction) {
+    (AbsenceState::Initial(InitialState::new()),
+     DialogAction::ProcessAndStop(Some(ReplyMessage::new(format!("Couldn't recognize time \"{}\". Canceled!", text),
+                                                         None)),
+                                  None,
+                                  None))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ChooseStartTimeState {}
+
+impl ChooseStartTimeState {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn try_process(&mut self, text: &str, _: &AbsenceKind, user_info: &mut UserInfo) -> (AbsenceState, DialogAction) {
+        let working_hours = get_working_hours(user_info);
+        match parse_time(text) {
+            // the event has to end on the same day
+            Some(start) if start.hour() >= LAST_END_HOUR => {
+                let question = format!("No end time is left after {}. From?", start.format("%H:%M"));
+                (AbsenceState::ChooseStartTime(ChooseStartTimeState::new()),
+                 DialogAction::ProcessAndContinue(Some(ReplyMessage::new(question,
+                                                                         Some(make_start_time_menu(&working_hours)))),
+                                                  None))
+            }
+            Some(start) => {
+                let menu = make_end_time_menu(&start, &working_hours);
+                (AbsenceState::ChooseEndTime(ChooseEndTimeState { start }),
+                 DialogAction::ProcessAndContinue(Some(ReplyMessage::new("Till?", Some(menu))), None))
+            }
+            None => stop_on_unrecognized_time(text),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ChooseEndTimeState {
+    start: NaiveTime,
+}
+
+impl ChooseEndTimeState {
+    fn try_process(&mut self, text: &str, kind: &AbsenceKind, user_info: &mut UserInfo) -> (AbsenceState, DialogAction) {
+        let working_hours = get_working_hours(user_info);
+        match parse_time(text) {
+            Some(end) if end <= self.start => {
+                let question = format!("End should be after {}. Till?", self.start.format("%H:%M"));
+                (AbsenceState::ChooseEndTime(self.clone()),
+                 DialogAction::ProcessAndContinue(Some(ReplyMessage::new(question,
+                                                                         Some(make_end_time_menu(&self.start,
+                                                                                                 &working_hours)))),
+                                                  None))
+            }
+            Some(end) => {
+                let zone = user_info.get_time_zone();
+                let when = When::Between(zone.today().naive_local(), self.start, end);
+                confirm_when(&when, kind, &working_hours, &zone).unwrap_or_else(|| stop_on_unrecognized_time(text))
+            }
+            None => stop_on_unrecognized_time(text),
+        }
+    }
+}
+
+pub const DATES_FORMAT_HINT : &str = "Which dates? Send a single date like \"24.12\" or a range like \"24.12-28.12\"";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ChooseDatesState {}
+
+impl ChooseDatesState {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn try_process(&mut self, text: &str, kind: &AbsenceKind, user_info: &mut UserInfo) -> (AbsenceState, DialogAction) {
+        if let Some(weekdays) = parse_every(text) {
+            return confirm_weekly(&weekdays, kind);
+        }
+
+        let working_hours = get_working_hours(user_info);
+        let zone = user_info.get_time_zone();
+        match parse_when(text, &zone.today().naive_local())
+            .and_then(|when| confirm_when(&when, kind, &working_hours, &zone)) {
+            Some(result) => result,
+            None => (AbsenceState::Initial(InitialState::new()),
+                     DialogAction::ProcessAndStop(Some(ReplyMessage::new(format!("Couldn't recognize dates in \"{}\". Canceled!", text),
+                                                                         None)),
+                                                  None,
+                                                  None))
+        }
+    }
+}
+
+/// Boundary between morning and afternoon.
+fn get_midday(working_hours: &WorkingHours) -> NaiveTime {
+    let minutes = (working_hours.start + working_hours.end) * 30;
+    NaiveTime::from_hms(minutes / 60, minutes % 60, 0)
+}
+
+/// Resolves bounds of partial days using working hours,
+/// `None` if the period is empty or its bounds don't exist in `zone`.
+fn confirmation_for(when: &When, working_hours: &WorkingHours, zone: &UserTimeZone) -> Option<ConfirmationState> {
+    let state = match when {
+        &When::Days(start, end) => ConfirmationState::Dates(start, end),
+        &When::After(date, time) => ConfirmationState::Hours(date.and_time(time), date.and_hms(working_hours.end, 0, 0)),
+        &When::Before(date, time) => ConfirmationState::Hours(date.and_hms(working_hours.start, 0, 0), date.and_time(time)),
+        &When::Between(date, from, to) => ConfirmationState::Hours(date.and_time(from), date.and_time(to)),
+        &When::Morning(date) => {
+            ConfirmationState::Hours(date.and_hms(working_hours.start, 0, 0), date.and_time(get_midday(working_hours)))
+        }
+        &When::Afternoon(date) => {
+            ConfirmationState::Hours(date.and_time(get_midday(working_hours)), date.and_hms(working_hours.end, 0, 0))
+        }
+    };
+
+    match state {
+        ConfirmationState::Hours(ref start, ref end) if start >= end => None,
+        ConfirmationState::Hours(ref start, ref end) => {
+            zone.from_local_datetime(start)?;
+            zone.from_local_datetime(end)?;
+            Some(state.clone())
+        }
+        state => Some(state),
+    }
+}
+
+fn confirm_when(when: &When,
+                kind: &AbsenceKind,
+                working_hours: &WorkingHours,
+                zone: &UserTimeZone)
+                -> Option<(AbsenceState, DialogAction)> {
+    let state = confirmation_for(when, working_hours, zone)?;
+    let question = match state {
+        ConfirmationState::Dates(ref start, ref end) => describe_dates_range(start, end),
+        ConfirmationState::Hours(ref start, ref end) => describe_hours_range(start, end),
+        _ => return None,
+    };
+
+    Some((AbsenceState::Confirmation(state),
+          DialogAction::ProcessAndContinue(Some(ReplyMessage::new(format!("Confirm event {} {}?", kind.name, question),
+                                                                  Some(YES_NO_MENU.clone()))),
+                                           None)))
+}
+
+fn confirm_weekly(weekdays: &[Weekday], kind: &AbsenceKind) -> (AbsenceState, DialogAction) {
+    let days = weekdays.iter().map(|weekday| weekday.num_days_from_monday()).collect();
+    (AbsenceState::Confirmation(ConfirmationState::Weekly(days)),
+     DialogAction::ProcessAndContinue(Some(ReplyMessage::new(format!("Confirm event {} every {}?",
+                                                                     kind.name,
+                                                                     describe_weekdays(weekdays)),
+                                                             Some(YES_NO_MENU.clone()))),
+                                      None))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+enum ConfirmationState {
+    Today,
+    Tomorrow,
+    TodayBeforeNow,
+    TodayFromNow,
+    Dates(NaiveDate, NaiveDate),
+    Hours(NaiveDateTime, NaiveDateTime),
+    /// Days of week counted from monday
+    Weekly(Vec<u32>),
+}
+
+impl ConfirmationState {
+    fn try_process(&mut self, text: &str, kind: &AbsenceKind, user_info: &mut UserInfo) -> (AbsenceState, DialogAction) {
+        if text == "yes" {
+            let working_hours = get_working_hours(user_info);
+            let zone = user_info.get_time_zone();
+            // dialogs started by reminders don't check the name in advance
+            let name = match user_info.get_calendar_name() {
+                Some(name) => name,
+                None => return stop_on_missing_name(),
+            };
+            let (mut event, description) = match self {
+                &mut ConfirmationState::Today =>
+                    (CalendarEvent::WholeDay(make_for_today(kind, name, &zone)), "for today".to_string()),
+                &mut ConfirmationState::Tomorrow =>
+                    (CalendarEvent::WholeDay(make_for_tomorrow(kind, name, &zone)), "for tomorrow".to_string()),
+                &mut ConfirmationState::TodayBeforeNow => {
+                    match make_before_now(kind, name, &working_hours, &zone.now()) {
+                        Some(event) => (CalendarEvent::PartialDay(event), "today untill now".to_string()),
+                        None => return ask_again_on_no_hours(UNTILL_NOW),
+                    }
+                }
+                &mut ConfirmationState::TodayFromNow => {
+                    match make_from_now(kind, name, &working_hours, &zone.now()) {
+                        Some(event) => (CalendarEvent::PartialDay(event), "today from now".to_string()),
+                        None => return ask_again_on_no_hours(FROM_NOW),
+                    }
+                }
+                &mut ConfirmationState::Dates(ref start, ref end) =>
+                    (CalendarEvent::WholeDay(make_for_dates(kind, name, start, end, &zone)), describe_dates_range(start, end)),
+                &mut ConfirmationState::Hours(ref start, ref end) => {
+                    match make_for_hours(kind, name, start, end, &zone) {
+                        Some(event) => (CalendarEvent::PartialDay(event), describe_hours_range(start, end)),
+                        // the zone has changed since the confirmation
+                        None => return stop_on_unrecognized_time(&describe_hours_range(start, end)),
+                    }
+                }
+                &mut ConfirmationState::Weekly(ref days) => {
+                    let event = match make_weekly(kind, name, &weekdays_from_numbers(days), &zone) {
+                        Some(event) => CalendarEvent::WholeDay(event),
+                        None => {
+                            return (AbsenceState::Initial(InitialState::new()),
+                                    DialogAction::ProcessAndStop(Some(ReplyMessage::new("No days of week. Canceled!", None)),
+                                                                 None,
+                                                                 None))
+                        }
+                    };
+                    let description = describe_event(&event);
+                    (event, description)
+                }
+            };
+            event.set_time_zone(zone.get_name());
+
+            let applied = format!("{} {}", kind.name, description);
+            (AbsenceState::Initial(InitialState::new()),
+             DialogAction::ProcessAndStop(Some(ReplyMessage::new(format!("Applied: {}", applied), None)),
+                                          Some(Event::New(kind.name.clone(), event)),
+                                          Some(ChannelMessage::new(applied))))
+        } else {
+            (AbsenceState::Initial(InitialState::new()),
+             DialogAction::ProcessAndStop(Some(ReplyMessage::new("Canceled!", None)), None, None))
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+enum AbsenceState {
+    Initial(InitialState),
+    ChooseMode(ChooseModeStateState),
+    ChooseDates(ChooseDatesState),
+    ChooseStartTime(ChooseStartTimeState),
+    ChooseEndTime(ChooseEndTimeState),
+    Confirmation(ConfirmationState),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AbsenceDialog {
+    #[serde(default = "AbsenceKind::wfh")]
+    kind: AbsenceKind,
+    state: AbsenceState,
+}
+
+impl Dialog for AbsenceDialog {
+    fn try_process(&mut self, text: &str, user_info: &mut UserInfo) -> DialogAction {
+        let kind = &self.kind;
+        let (state, result) = match self.state {
+            AbsenceState::Initial(ref mut state) => state.try_process(text, kind, user_info),
+            AbsenceState::ChooseMode(ref mut state) => state.try_process(text, kind, user_info),
+            AbsenceState::ChooseDates(ref mut state) => state.try_process(text, kind, user_info),
+            AbsenceState::ChooseStartTime(ref mut state) => state.try_process(text, kind, user_info),
+            AbsenceState::ChooseEndTime(ref mut state) => state.try_process(text, kind, user_info),
+            AbsenceState::Confirmation(ref mut state) => state.try_process(text, kind, user_info),
+        };
+
+        self.state = state;
+
+        result
+    }
+
+    fn make(initial_message: &str, user_info: &mut UserInfo) -> DialogInitializationResult {
+        let kind = match find_absence_kind(initial_message) {
+            Some(kind) => kind,
+            None => return DialogInitializationResult::NotProcessed,
+        };
+
+        let mut dialog = Self { kind, state: AbsenceState::Initial(InitialState::new()) };
+        let result = dialog.try_process(initial_message, user_info);
+        match result {
+            DialogAction::ProcessAndContinue(reply, event) => {
+                DialogInitializationResult::StartedProcessing(reply, event, Box::new(dialog))
+            }
+            DialogAction::ProcessAndStop(reply, event, _) => {
+                DialogInitializationResult::Finished(reply, event)
+            }
+            DialogAction::Stop => DialogInitializationResult::NotProcessed,
+        }
+    }
+
+    /// One command per configured absence kind.
+    fn get_commands() -> Vec<CommandDescription> {
+        get_absence_kinds()
+            .into_iter()
+            .map(|kind| {
+                     let examples = ["", " tomorrow", " 24.12-28.12", " today after 14:00", " every friday"];
+                     examples.iter()
+                         .fold(CommandDescription::new(kind.command.clone(), format!("Report {}", kind.name)),
+                               |description, example| description.with_example(format!("{}{}", kind.command, example)))
+                 })
+            .collect()
+    }
+}
+
+impl DynamicSerializable for AbsenceDialog {
+    fn to_string(&self) -> String {
+        serde_json::to_string(self).unwrap() // FIXME
+    }
+
+    fn from_string(string: &str) -> Self {
+        serde_json::from_str::<Self>(string).unwrap() // FIXME
+    }
+}
+
+impl StaticNameGetter for AbsenceDialog {
+    fn get_name() -> &'static str {
+        // Kept from the time when WFH was the only kind so that saved dialogs can be loaded
+        return "wfh-dialog";
+    }
+}
+
+/// Dialog of the first absence kind waiting for the answer to `WHEN_MENU` and the kind name.
+pub fn make_when_dialog() -> Option<(AbsenceDialog, String)> {
+    let kind = get_absence_kinds().into_iter().next()?;
+    let name = kind.name.clone();
+    Some((AbsenceDialog { kind, state: AbsenceState::ChooseMode(ChooseModeStateState::new()) }, name))
+}
+
+/// Same event moved to the new period.
+pub fn make_moved_event(event: &CalendarEvent,
+                        when: &When,
+                        working_hours: &WorkingHours,
+                        zone: &UserTimeZone)
+                        -> Option<CalendarEvent> {
+    let summary = event.get_summary().clone();
+    let mut moved_event = match confirmation_for(when, working_hours, zone)? {
+        ConfirmationState::Dates(ref start, ref end) =>
+            CalendarEvent::WholeDay(WholeDayEvent::new(summary,
+                                                       &zone.from_local_date(start),
+                                                       &zone.from_local_date(end))),
+        ConfirmationState::Hours(ref start, ref end) =>
+            CalendarEvent::PartialDay(PartialDayEvent::new(summary,
+                                                           &zone.from_local_datetime(start)?,
+                                                           &zone.from_local_datetime(end)?)),
+        _ => return None,
+    };
+    moved_event.set_time_zone(zone.get_name());
+    Some(moved_event)
+}
+
+pub fn describe_event(event: &CalendarEvent) -> String {
+    match event {
+        &CalendarEvent::WholeDay(ref event) if event.is_recurring() => {
+            let mut result = format!("every {} from {}",
+                                     describe_weekdays(&event.get_weekdays()),
+                                     event.get_start_date().format("%d.%m.%Y"));
+            if let Some(end) = event.get_recurrence_end() {
+                result.push_str(&format!(" till {}", end.format("%d.%m.%Y")));
+            }
+            result
+        }
+        &CalendarEvent::WholeDay(ref event) => describe_dates_range(&event.get_start_date(), &event.get_end_date()),
+        &CalendarEvent::PartialDay(ref event) => describe_hours_range(&event.get_start_time(), &event.get_end_time()),
+    }
+}
+
+fn make_whole_day_event(kind: &AbsenceKind, name: &str,  start_date: &LocalDate, end_date: &LocalDate) -> WholeDayEvent {
+    WholeDayEvent::new(format!("{}: {}", kind.calendar_prefix, name), start_date, end_date)
+}
+
+fn make_single_day(kind: &AbsenceKind, name: &str,  date: &LocalDate) -> WholeDayEvent {
+    make_whole_day_event(kind, name, date, date)
+}
+
+fn make_for_today(kind: &AbsenceKind, name: &str, zone: &UserTimeZone) -> WholeDayEvent {
+    make_single_day(kind, name, &zone.today())
+}
+
+fn make_for_tomorrow(kind: &AbsenceKind, name: &str, zone: &UserTimeZone) -> WholeDayEvent {
+    make_single_day(kind, name, &(zone.today() + time::Duration::days(1)))
+}
+
+fn make_for_dates(kind: &AbsenceKind,
+                  name: &str,
+                  start_date: &NaiveDate,
+                  end_date: &NaiveDate,
+                  zone: &UserTimeZone)
+                  -> WholeDayEvent {
+    make_whole_day_event(kind, name, &zone.from_local_date(start_date), &zone.from_local_date(end_date))
+}
+
+/// `None` if no weekdays are given.
+fn make_weekly(kind: &AbsenceKind, name: &str, weekdays: &[Weekday], zone: &UserTimeZone) -> Option<WholeDayEvent> {
+    let start = next_of_weekdays(&zone.today().naive_local(), weekdays)?;
+    Some(WholeDayEvent::new_weekly(format!("{}: {}", kind.calendar_prefix, name),
+                                   &zone.from_local_date(&start),
+                                   weekdays))
+}
+
+fn make_for_hours(kind: &AbsenceKind,
+                  name: &str,
+                  start_time: &NaiveDateTime,
+                  end_time: &NaiveDateTime,
+                  zone: &UserTimeZone)
+                  -> Option<PartialDayEvent> {
+    Some(make_partial_day_event(kind,
+                                name,
+                                &zone.from_local_datetime(start_time)?,
+                                &zone.from_local_datetime(end_time)?))
+}
+
+fn make_partial_day_event(kind: &AbsenceKind, name: &str, start_time: &LocalDateTime, end_time: &LocalDateTime) -> PartialDayEvent {
+    PartialDayEvent::new(format!("{}: {}", kind.calendar_prefix, name), start_time, end_time)
+}
+
+fn make_same_with_hours(time: &LocalDateTime, hours: u32) -> LocalDateTime {
+    time.with_hour(hours).unwrap().with_minute(0).
+        unwrap().with_second(0).unwrap().with_nanosecond(0).unwrap()
+}
+
+/// From the start of working hours, None before they start.
+fn get_before_now(working_hours: &WorkingHours, now: &LocalDateTime) -> Option<(LocalDateTime, LocalDateTime)> {
+    let day_start = make_same_with_hours(now, working_hours.start);
+    if day_start < *now { Some((day_start, *now)) } else { None }
+}
+
+/// Till the end of working hours, None after they end.
+fn get_from_now(working_hours: &WorkingHours, now: &LocalDateTime) -> Option<(LocalDateTime, LocalDateTime)> {
+    let day_end = make_same_with_hours(now, working_hours.end);
+    if *now < day_end { Some((*now, day_end)) } else { None }
+}
+
+fn make_before_now(kind: &AbsenceKind, name: &str, working_hours: &WorkingHours, now: &LocalDateTime)
+                   -> Option<PartialDayEvent> {
+    get_before_now(working_hours, now).map(|(start, end)| make_partial_day_event(kind, name, &start, &end))
+}
+
+fn make_from_now(kind: &AbsenceKind, name: &str, working_hours: &WorkingHours, now: &LocalDateTime)
+                 -> Option<PartialDayEvent> {
+    get_from_now(working_hours, now).map(|(start, end)| make_partial_day_event(kind, name, &start, &end))
+}
+
+const WEEKDAYS: [(Weekday, &str); 7] = [(Weekday::Mon, "monday"),
+                                        (Weekday::Tue, "tuesday"),
+                                        (Weekday::Wed, "wednesday"),
+                                        (Weekday::Thu, "thursday"),
+                                        (Weekday::Fri, "friday"),
+                                        (Weekday::Sat, "saturday"),
+                                        (Weekday::Sun, "sunday")];
+
+fn weekdays_from_numbers(numbers: &[u32]) -> Vec<Weekday> {
+    numbers.iter()
+        .filter_map(|number| WEEKDAYS.get(*number as usize))
+        .map(|&(weekday, _)| weekday)
+        .collect()
+}
+
+/// "monday, friday"
+fn describe_weekdays(weekdays: &[Weekday]) -> String {
+    let names: Vec<&str> = WEEKDAYS.iter()
+        .filter(|&&(weekday, _)| weekdays.contains(&weekday))
+        .map(|&(_, name)| name)
+        .collect();
+    names.join(", ")
+}
+
+fn describe_dates_range(start: &NaiveDate, end: &NaiveDate) -> String {
+    if start == end {
+        format!("for {}", start.format("%d.%m.%Y"))
+    } else {
+        format!("from {} to {}", start.format("%d.%m.%Y"), end.format("%d.%m.%Y"))
+    }
+}
+
+fn describe_hours_range(start: &NaiveDateTime, end: &NaiveDateTime) -> String {
+    format!("on {} from {} to {}", start.format("%d.%m.%Y"), start.format("%H:%M"), end.format("%H:%M"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use user_data::ChatID;
+
+    use chrono::{FixedOffset, TimeZone};
+
+    #[test]
+    fn test_passed_working_hours() {
+        let kind = AbsenceKind::wfh();
+        let working_hours = WorkingHours { start: 9, end: 20 };
+        let zone = FixedOffset::east(0);
+        let early = zone.ymd(2030, 12, 24).and_hms(7, 30, 0);
+        let late = zone.ymd(2030, 12, 24).and_hms(21, 0, 0);
+
+        assert!(make_before_now(&kind, "V.Pupkin", &working_hours, &early).is_none());
+        assert!(make_from_now(&kind, "V.Pupkin", &working_hours, &late).is_none());
+        let event = make_from_now(&kind, "V.Pupkin", &working_hours, &early).unwrap();
+        assert_eq!((event.get_start(), event.get_end()), (early, zone.ymd(2030, 12, 24).and_hms(20, 0, 0)));
+        let event = make_before_now(&kind, "V.Pupkin", &working_hours, &late).unwrap();
+        assert_eq!((event.get_start(), event.get_end()), (zone.ymd(2030, 12, 24).and_hms(9, 0, 0), late));
+    }
+
+    #[test]
+    fn test_confirmation_without_name() {
+        let mut dialog = AbsenceDialog {
+            kind: AbsenceKind::wfh(),
+            state: AbsenceState::Confirmation(ConfirmationState::Today),
+        };
+        let mut user_info = UserInfo::new(ChatID::new(42), "Vasiliy".to_string(), String::new());
+        match dialog.try_process("yes", &mut user_info) {
+            DialogAction::ProcessAndStop(Some(ref reply), None, None) => {
+                assert_eq!(reply.text, "Please specify your calendar name using /setmyname")
+            }
+            _ => panic!("The event is posted without a name"),
+        }
+    }
+}
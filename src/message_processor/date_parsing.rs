@@ -0,0 +1,319 @@
+use chrono::{Datelike, NaiveDate, NaiveTime, Weekday};
+use time;
+
+/// Period described by a free-text expression like "mon-wed", "24.12" or "tomorrow after 14:00".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum When {
+    Days(NaiveDate, NaiveDate),
+    After(NaiveDate, NaiveTime),
+    Before(NaiveDate, NaiveTime),
+    Between(NaiveDate, NaiveTime, NaiveTime),
+    /// First half of working hours
+    Morning(NaiveDate),
+    /// Second half of working hours
+    Afternoon(NaiveDate),
+}
+
+const TIME_KEYWORDS: [&str; 7] = ["after", "before", "till", "until", "from", "morning", "afternoon"];
+
+/// Parses expressions of the form `<days> [<time>]`, where
+/// `<days>` is `<day>`, `<day> - <day>` or `<day> to <day>`,
+/// `<day>` is "today", "tomorrow", a weekday ("fri", "friday"), "next <weekday>"
+/// or a date "DD.MM[.YYYY]", and `<time>` is "after HH[:MM]", "before HH[:MM]" (also "till",
+/// "until"), "from HH[:MM] to HH[:MM]", "morning" or "afternoon". Time is allowed only for
+/// a single day, the day may be omitted for today.
+pub fn parse_when(text: &str, today: &NaiveDate) -> Option<When> {
+    let normalized = text.to_lowercase().replace("-", " - ");
+    let tokens: Vec<&str> = normalized.split_whitespace().collect();
+    let mut parser = Parser { tokens: &tokens, position: 0 };
+
+    let start = match tokens.first() {
+        Some(token) if TIME_KEYWORDS.contains(token) => *today,
+        _ => parser.parse_day(today)?,
+    };
+    let result = if parser.skip_any(&["-", "to"]) {
+        let end = parser.parse_day(&start)?;
+        if end < start {
+            return None;
+        }
+        When::Days(start, end)
+    } else if parser.skip_any(&["after"]) {
+        When::After(start, parser.parse_time()?)
+    } else if parser.skip_any(&["before", "till", "until"]) {
+        When::Before(start, parser.parse_time()?)
+    } else if parser.skip_any(&["from"]) {
+        let from = parser.parse_time()?;
+        if !parser.skip_any(&["-", "to"]) {
+            return None;
+        }
+        let to = parser.parse_time()?;
+        if to <= from {
+            return None;
+        }
+        When::Between(start, from, to)
+    } else if parser.skip_any(&["morning"]) {
+        When::Morning(start)
+    } else if parser.skip_any(&["afternoon"]) {
+        When::Afternoon(start)
+    } else {
+        When::Days(start, start)
+    };
+
+    if parser.is_finished() { Some(result) } else { None }
+}
+
+/// Parses time "HH[:MM]".
+pub fn parse_time(text: &str) -> Option<NaiveTime> {
+    let tokens = [text.trim()];
+    let mut parser = Parser { tokens: &tokens, position: 0 };
+    parser.parse_time()
+}
+
+/// Parses weekly recurrence like "every friday" or "every mon, thu and fri".
+pub fn parse_every(text: &str) -> Option<Vec<Weekday>> {
+    let normalized = text.to_lowercase().replace(",", " ");
+    let mut tokens = normalized.split_whitespace();
+    if tokens.next() != Some("every") {
+        return None;
+    }
+
+    let mut weekdays = Vec::new();
+    for token in tokens.filter(|token| *token != "and") {
+        let weekday = parse_weekday(token)?;
+        if !weekdays.contains(&weekday) {
+            weekdays.push(weekday);
+        }
+    }
+
+    if weekdays.is_empty() { None } else { Some(weekdays) }
+}
+
+/// First date not earlier than `after` which falls on one of `weekdays`.
+pub fn next_of_weekdays(after: &NaiveDate, weekdays: &[Weekday]) -> Option<NaiveDate> {
+    weekdays.iter().map(|weekday| next_weekday(after, *weekday)).min()
+}
+
+struct Parser<'a> {
+    tokens: &'a [&'a str],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.tokens.get(self.position).cloned();
+        if token.is_some() {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn skip_any(&mut self, expected: &[&str]) -> bool {
+        match self.tokens.get(self.position) {
+            Some(token) if expected.contains(token) => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.position == self.tokens.len()
+    }
+
+    /// Parses a day not earlier than `after`.
+    fn parse_day(&mut self, after: &NaiveDate) -> Option<NaiveDate> {
+        match self.next()? {
+            "today" => Some(*after),
+            "tomorrow" => Some(*after + time::Duration::days(1)),
+            "next" => {
+                let weekday = parse_weekday(self.next()?)?;
+                let next_monday = *after +
+                                  time::Duration::days(7 -
+                                                       after.weekday().num_days_from_monday() as i64);
+                Some(next_weekday(&next_monday, weekday))
+            }
+            token => {
+                match parse_weekday(token) {
+                    Some(weekday) => Some(next_weekday(after, weekday)),
+                    None => parse_date(token, after),
+                }
+            }
+        }
+    }
+
+    fn parse_time(&mut self) -> Option<NaiveTime> {
+        let token = self.next()?;
+        let mut parts = token.splitn(2, ':');
+        let hours = parts.next()?.parse().ok()?;
+        let minutes = match parts.next() {
+            Some(minutes) if minutes.len() == 2 => minutes.parse().ok()?,
+            Some(_) => return None,
+            None => 0,
+        };
+        NaiveTime::from_hms_opt(hours, minutes, 0)
+    }
+}
+
+fn parse_weekday(token: &str) -> Option<Weekday> {
+    match token {
+        "mon" | "monday" => Some(Weekday::Mon),
+        "tue" | "tues" | "tuesday" => Some(Weekday::Tue),
+        "wed" | "wednesday" => Some(Weekday::Wed),
+        "thu" | "thur" | "thurs" | "thursday" => Some(Weekday::Thu),
+        "fri" | "friday" => Some(Weekday::Fri),
+        "sat" | "saturday" => Some(Weekday::Sat),
+        "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn next_weekday(after: &NaiveDate, weekday: Weekday) -> NaiveDate {
+    let days = (7 + weekday.num_days_from_monday() as i64 -
+                after.weekday().num_days_from_monday() as i64) % 7;
+    *after + time::Duration::days(days)
+}
+
+/// Parses "DD.MM" (the nearest such date not earlier than `after`) or "DD.MM.YYYY".
+fn parse_date(token: &str, after: &NaiveDate) -> Option<NaiveDate> {
+    let parts: Vec<&str> = token.split('.').collect();
+    let numbers: Vec<u32> = parts.iter().filter_map(|part| part.parse().ok()).collect();
+    if numbers.len() != parts.len() {
+        return None;
+    }
+
+    match numbers.as_slice() {
+        &[day, month] => {
+            NaiveDate::from_ymd_opt(after.year(), month, day).and_then(|date| if date < *after {
+                NaiveDate::from_ymd_opt(after.year() + 1, month, day)
+            } else {
+                Some(date)
+            })
+        }
+        &[day, month, year] if year >= 1000 => NaiveDate::from_ymd_opt(year as i32, month, day),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Wednesday
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd(2017, 12, 20)
+    }
+
+    fn date(day: u32, month: u32, year: i32) -> NaiveDate {
+        NaiveDate::from_ymd(year, month, day)
+    }
+
+    fn hm(hours: u32, minutes: u32) -> NaiveTime {
+        NaiveTime::from_hms(hours, minutes, 0)
+    }
+
+    fn parse(text: &str) -> Option<When> {
+        parse_when(text, &today())
+    }
+
+    #[test]
+    fn test_relative_days() {
+        assert_eq!(parse("today"), Some(When::Days(today(), today())));
+        assert_eq!(parse("Tomorrow"),
+                   Some(When::Days(date(21, 12, 2017), date(21, 12, 2017))));
+    }
+
+    #[test]
+    fn test_weekdays() {
+        assert_eq!(parse("wed"), Some(When::Days(today(), today())));
+        assert_eq!(parse("friday"),
+                   Some(When::Days(date(22, 12, 2017), date(22, 12, 2017))));
+        assert_eq!(parse("mon"),
+                   Some(When::Days(date(25, 12, 2017), date(25, 12, 2017))));
+        assert_eq!(parse("next friday"),
+                   Some(When::Days(date(29, 12, 2017), date(29, 12, 2017))));
+        assert_eq!(parse("next mon"),
+                   Some(When::Days(date(25, 12, 2017), date(25, 12, 2017))));
+        assert_eq!(parse("next"), None);
+        assert_eq!(parse("next week"), None);
+    }
+
+    #[test]
+    fn test_dates() {
+        assert_eq!(parse("24.12"),
+                   Some(When::Days(date(24, 12, 2017), date(24, 12, 2017))));
+        assert_eq!(parse("10.01"),
+                   Some(When::Days(date(10, 1, 2018), date(10, 1, 2018))));
+        assert_eq!(parse("01.02.2019"),
+                   Some(When::Days(date(1, 2, 2019), date(1, 2, 2019))));
+        assert_eq!(parse("31.02"), None);
+        assert_eq!(parse("24"), None);
+        assert_eq!(parse("24.12.19"), None);
+        assert_eq!(parse("24.12.2017.1"), None);
+    }
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(parse("mon-wed"),
+                   Some(When::Days(date(25, 12, 2017), date(27, 12, 2017))));
+        assert_eq!(parse("fri - mon"),
+                   Some(When::Days(date(22, 12, 2017), date(25, 12, 2017))));
+        assert_eq!(parse("24.12-28.12"),
+                   Some(When::Days(date(24, 12, 2017), date(28, 12, 2017))));
+        assert_eq!(parse("30.12 to 02.01"),
+                   Some(When::Days(date(30, 12, 2017), date(2, 1, 2018))));
+        assert_eq!(parse("today-tomorrow"),
+                   Some(When::Days(today(), date(21, 12, 2017))));
+        assert_eq!(parse("28.12.2017-24.12.2017"), None);
+        assert_eq!(parse("mon-"), None);
+    }
+
+    #[test]
+    fn test_time_bounds() {
+        assert_eq!(parse("tomorrow after 14:00"),
+                   Some(When::After(date(21, 12, 2017), hm(14, 0))));
+        assert_eq!(parse("today before 11"),
+                   Some(When::Before(today(), hm(11, 0))));
+        assert_eq!(parse("fri from 10:30 to 13:00"),
+                   Some(When::Between(date(22, 12, 2017), hm(10, 30), hm(13, 0))));
+        assert_eq!(parse("24.12 from 10-12"),
+                   Some(When::Between(date(24, 12, 2017), hm(10, 0), hm(12, 0))));
+        assert_eq!(parse("today from 13:00 to 10:00"), None);
+        assert_eq!(parse("today after 25:00"), None);
+        assert_eq!(parse("today after 14:5"), None);
+        assert_eq!(parse("today after"), None);
+        assert_eq!(parse("mon-wed after 14:00"), None);
+        assert_eq!(parse("tomorrow morning"), Some(When::Morning(date(21, 12, 2017))));
+        assert_eq!(parse("fri afternoon"), Some(When::Afternoon(date(22, 12, 2017))));
+        assert_eq!(parse("from 10 to 12"), Some(When::Between(today(), hm(10, 0), hm(12, 0))));
+        assert_eq!(parse("afternoon"), Some(When::Afternoon(today())));
+        assert_eq!(parse("morning afternoon"), None);
+    }
+
+    #[test]
+    fn test_time() {
+        assert_eq!(parse_time("10:30"), Some(hm(10, 30)));
+        assert_eq!(parse_time(" 9 "), Some(hm(9, 0)));
+        assert_eq!(parse_time("24:00"), None);
+        assert_eq!(parse_time("10 30"), None);
+    }
+
+    #[test]
+    fn test_every() {
+        assert_eq!(parse_every("every friday"), Some(vec![Weekday::Fri]));
+        assert_eq!(parse_every("Every mon, thu and fri"),
+                   Some(vec![Weekday::Mon, Weekday::Thu, Weekday::Fri]));
+        assert_eq!(parse_every("every"), None);
+        assert_eq!(parse_every("every day"), None);
+        assert_eq!(parse_every("friday"), None);
+        assert_eq!(next_of_weekdays(&today(), &[Weekday::Mon, Weekday::Fri]),
+                   Some(date(22, 12, 2017)));
+    }
+
+    #[test]
+    fn test_garbage() {
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("someday"), None);
+        assert_eq!(parse("today please"), None);
+    }
+}
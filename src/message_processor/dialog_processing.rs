@@ -1,18 +1,20 @@
-use basic_structures::{WholeDayEvent, PartialDayEvent, Menu};
-use user_data::UserInfo;
+use basic_structures::{CalendarEvent, Menu};
+use user_data::{UserInfo, UserEvent};
+use chrono::NaiveDate;
 use serde::{Serialize, Serializer};
 use serde::ser::SerializeSeq;
-use serde::de::{Deserialize, Deserializer, Visitor, SeqAccess};
+use serde::de::{Deserialize, Deserializer, Error, Visitor, SeqAccess};
 
 use std::fmt::{Formatter};
 use std::fmt;
 use std::sync::Mutex;
-use std::collections::hash_map::HashMap;
 use std::ops::Deref;
 
 pub enum Event {
-    WholeDay(WholeDayEvent),
-    PartialDay(PartialDayEvent)
+    /// Event of the absence kind with the given name
+    New(String, CalendarEvent),
+    Update(UserEvent, CalendarEvent),
+    Delete(UserEvent),
 }
 
 pub struct ReplyMessage {
@@ -80,10 +82,42 @@ pub trait ClonableToDialog {
     fn clone_to_dialog(&self) -> Box<Dialog>;
 }
 
+/// Command starting a dialog.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandDescription {
+    /// E.g. "/wfh"
+    pub command: String,
+    pub description: String,
+    /// Messages shown in the help of the command
+    pub examples: Vec<String>,
+}
+
+impl CommandDescription {
+    pub fn new<C, D>(command: C, description: D) -> Self
+        where C: Into<String>,
+              D: Into<String>
+    {
+        Self {
+            command: command.into(),
+            description: description.into(),
+            examples: Vec::new(),
+        }
+    }
+
+    pub fn with_example<S>(mut self, example: S) -> Self
+        where S: Into<String>
+    {
+        self.examples.push(example.into());
+        self
+    }
+}
+
 pub trait Dialog: DynamicSerializable + ClonableToDialog {
     fn try_process(&mut self, text: &str, user_info: &mut UserInfo) -> DialogAction;
     fn make(initial_message: &str, user_info: &mut UserInfo) -> DialogInitializationResult
         where Self: Sized;
+    /// `make` is called only for messages starting with one of these commands.
+    fn get_commands() -> Vec<CommandDescription> where Self: Sized;
 }
 
 impl<T> ClonableToDialog for T where T: 'static + Dialog + Clone {
@@ -96,19 +130,135 @@ lazy_static! {
     pub static ref YES_NO_MENU : Menu = vec!(vec!("yes".into(), "no".into()));
 }
 
-type DeserializersMap = HashMap<&'static str, Box<Fn(&str) -> Box<Dialog> + Send>>;
-type DeserializersMapProtected = Mutex<DeserializersMap>;
+lazy_static! {
+    static ref DIALOG_TIMEOUT_SECONDS: Mutex<i64> = Mutex::new(60 * 60);
+}
+
+pub fn register_dialog_timeout(minutes: u32) {
+    *DIALOG_TIMEOUT_SECONDS.lock().unwrap() = i64::from(minutes) * 60;
+}
+
+/// Whether the dialog idle since `last_activity` should be dropped at `now`.
+pub fn is_expired(last_activity: i64, now: i64) -> bool {
+    now - last_activity > *DIALOG_TIMEOUT_SECONDS.lock().unwrap()
+}
+
+/// Answer to the message from the team of the sender, records of all users and today.
+pub type QueryAnswer = fn(&str, Option<&String>, &[&UserInfo], &NaiveDate) -> String;
+
+/// Commands handled without dialogs.
+#[derive(Clone, Copy)]
+pub enum BuiltinCommand {
+    /// Answered from the records of all users
+    Query(QueryAnswer),
+    /// Stops the active dialog
+    Cancel,
+}
+
+enum Handler {
+    Dialog {
+        make: fn(&str, &mut UserInfo) -> DialogInitializationResult,
+        deserialize: fn(&str) -> Box<Dialog>,
+    },
+    Builtin(BuiltinCommand),
+}
+
+/// Everything needed to handle commands of some type or to load its dialog from the saved state.
+struct CommandType {
+    name: &'static str,
+    get_commands: fn() -> Vec<CommandDescription>,
+    handler: Handler,
+}
+
+impl CommandType {
+    fn accepts(&self, command: &str) -> bool {
+        (self.get_commands)().iter().any(|description| description.command == command)
+    }
+}
 
 lazy_static! {
-    static ref DESERIALIZERS: DeserializersMapProtected = DeserializersMapProtected::new(DeserializersMap::new());
+    static ref COMMAND_TYPES: Mutex<Vec<CommandType>> = Mutex::new(Vec::new());
 }
 
+fn deserialize_dialog<T>(string: &str) -> Box<Dialog>
+    where T: 'static + Dialog
+{
+    Box::new(T::from_string(string))
+}
+
+fn register(command_type: CommandType) {
+    let mut command_types = COMMAND_TYPES.lock().unwrap();
+    if command_types.iter().all(|other| other.name != command_type.name) {
+        command_types.push(command_type);
+    }
+}
+
+/// Dialogs are tried in the order of registration, repeated registrations are ignored.
 pub fn register_dialog<T>()
     where T: 'static + Dialog
 {
-    let mut deserializers = DESERIALIZERS.lock().unwrap();
-    deserializers.insert(T::get_name(),
-                         Box::new(|string: &str| Box::new(T::from_string(string))));
+    register(CommandType {
+                 name: T::get_name(),
+                 get_commands: T::get_commands,
+                 handler: Handler::Dialog {
+                     make: T::make,
+                     deserialize: deserialize_dialog::<T>,
+                 },
+             });
+}
+
+/// Registered along with dialogs, so that the help lists them in the same order.
+pub fn register_builtin(name: &'static str, get_commands: fn() -> Vec<CommandDescription>, command: BuiltinCommand) {
+    register(CommandType {
+                 name,
+                 get_commands,
+                 handler: Handler::Builtin(command),
+             });
+}
+
+/// Commands of all registered dialogs and builtin commands in the order of registration.
+pub fn get_commands() -> Vec<CommandDescription> {
+    COMMAND_TYPES.lock()
+        .unwrap()
+        .iter()
+        .flat_map(|command_type| (command_type.get_commands)())
+        .collect()
+}
+
+/// Builtin command of the message, `None` for messages handled by dialogs.
+pub fn find_builtin(message: &str) -> Option<BuiltinCommand> {
+    let command = message.split_whitespace().next().unwrap_or("");
+    COMMAND_TYPES.lock()
+        .unwrap()
+        .iter()
+        .filter(|command_type| command_type.accepts(command))
+        .filter_map(|command_type| match command_type.handler {
+                        Handler::Builtin(builtin) => Some(builtin),
+                        Handler::Dialog { .. } => None,
+                    })
+        .next()
+}
+
+/// Starts the first registered dialog which accepts the message.
+pub fn make_dialog(message: &str, user_info: &mut UserInfo) -> DialogInitializationResult {
+    let command = message.split_whitespace().next().unwrap_or("");
+    let makers: Vec<_> = COMMAND_TYPES.lock()
+        .unwrap()
+        .iter()
+        .filter(|command_type| command_type.accepts(command))
+        .filter_map(|command_type| match command_type.handler {
+                        Handler::Dialog { make, .. } => Some(make),
+                        Handler::Builtin(_) => None,
+                    })
+        .collect();
+
+    for make in makers {
+        match make(message, user_info) {
+            DialogInitializationResult::NotProcessed => continue,
+            result => return result,
+        }
+    }
+    DialogInitializationResult::NotProcessed
 }
 
 impl Serialize for Box<Dialog> {
@@ -140,17 +290,47 @@ impl<'de> Deserialize<'de> for Box<Dialog> {
             fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
                 where A: SeqAccess<'de>
             {
-                //FIXME
-                let name = seq.next_element::<String>()?.unwrap();
-                let data = seq.next_element::<String>()?.unwrap();
+                let name = seq.next_element::<String>()?.ok_or_else(|| A::Error::invalid_length(0, &self))?;
+                let data = seq.next_element::<String>()?.ok_or_else(|| A::Error::invalid_length(1, &self))?;
                 Ok((name, data))
             }
         }
 
         let (type_name, data) = deserializer.deserialize_seq(SequenceVisitor {})?;
-        let deserializers = DESERIALIZERS.lock().unwrap();
-        let dialog_deserializer = deserializers.get(type_name.as_str()).unwrap(); //FIXME
-        Ok(dialog_deserializer(data.as_str()))
+        match find_deserialize(&type_name) {
+            Some(deserialize) => Ok(deserialize(data.as_str())),
+            None => Err(D::Error::custom(format!("unknown dialog \"{}\"", type_name))),
+        }
+    }
+}
+
+fn find_deserialize(type_name: &str) -> Option<fn(&str) -> Box<Dialog>> {
+    COMMAND_TYPES.lock()
+        .unwrap()
+        .iter()
+        .filter(|command_type| command_type.name == type_name)
+        .filter_map(|command_type| match command_type.handler {
+                        Handler::Dialog { deserialize, .. } => Some(deserialize),
+                        Handler::Builtin(_) => None,
+                    })
+        .next()
+}
+
+/// Dialogs unknown to this build, e.g. renamed ones, are dropped instead of failing to load the whole state.
+pub fn deserialize_active_dialog<'de, D>(deserializer: D) -> Result<Option<Box<Dialog>>, D::Error>
+    where D: Deserializer<'de>
+{
+    match Option::<(String, String)>::deserialize(deserializer)? {
+        Some((type_name, data)) => {
+            match find_deserialize(&type_name) {
+                Some(deserialize) => Ok(Some(deserialize(data.as_str()))),
+                None => {
+                    warn!("Dropped dialog of unknown type \"{}\"", type_name);
+                    Ok(None)
+                }
+            }
+        }
+        None => Ok(None),
     }
 }
 
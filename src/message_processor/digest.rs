@@ -0,0 +1,43 @@
+use message_processor::queries::describe_absences;
+use message_processor::teams::get_status_channel;
+use user_data::UserInfo;
+
+use chrono::{NaiveDate, NaiveDateTime, Timelike};
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+lazy_static! {
+    static ref DIGEST_HOUR: Mutex<Option<u32>> = Mutex::new(None);
+}
+
+pub fn register_digest_hour(hour: Option<u32>) {
+    *DIGEST_HOUR.lock().unwrap() = hour;
+}
+
+pub fn is_digest_time(now: &NaiveDateTime) -> bool {
+    *DIGEST_HOUR.lock().unwrap() == Some(now.hour())
+}
+
+/// Status channel and digest text like "Today: WFH – A.Smith, J.Doe; Vacation – K.Lee"
+/// for each channel which has somebody away on `date`.
+pub fn make_digests<'a, I>(users: I, date: &NaiveDate) -> Vec<(String, String)>
+    where I: Iterator<Item = &'a UserInfo>
+{
+    let mut channels = BTreeMap::<String, Vec<&UserInfo>>::new();
+    for user_info in users {
+        channels.entry(get_status_channel(user_info.team.as_ref()))
+            .or_insert_with(Vec::new)
+            .push(user_info);
+    }
+
+    let mut result = Vec::new();
+    for (channel, users) in channels {
+        let absences = describe_absences(users.iter().cloned(), date, date);
+        if !absences.is_empty() {
+            result.push((channel, format!("Today: {}", absences.join("; "))));
+        }
+    }
+
+    result
+}
@@ -1,75 +1,164 @@
 use super::basic_structures::*;
 use super::user_data::*;
 use super::save_load_state::*;
+use super::configuration::Configuration;
+use super::outbox::{Outbox, PendingEvent, make_event_id};
+
+use chrono::{DateTime, Utc, NaiveDate, NaiveDateTime};
+use chrono;
 
 use std::cell::RefCell;
 use std::ops::DerefMut;
 use std::collections::HashMap;
 use std::mem;
+use std::error::Error;
+use std::io;
 
 use self::dialog_processing::{ReplyMessage, Dialog, DialogAction, Event, ChannelMessage,
-                              DialogInitializationResult};
-use self::wfh::WfhDialog;
-use self::simple_dialogs::{HelpDialog, WhoAmIDialog, SetMyNameDialog};
+                              DialogInitializationResult, BuiltinCommand};
+use self::absence::{AbsenceDialog, WHEN_MENU};
+use self::my_events::MyEventsDialog;
+use self::teams::SetTeamDialog;
+use self::reminders::RemindersDialog;
+use self::simple_dialogs::{HelpDialog, WhoAmIDialog, SetMyNameDialog, SetTimeZoneDialog};
+
+pub use self::dialog_processing::CommandDescription;
 
 mod dialog_processing;
-mod wfh;
+mod date_parsing;
+mod absence;
+mod my_events;
 mod simple_dialogs;
+mod teams;
+mod digest;
+mod queries;
+mod reminders;
+
+const CANCEL_COMMAND: &str = "/cancel";
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct DialogsProcessor {
+    #[serde(default, deserialize_with = "dialog_processing::deserialize_active_dialog")]
     active_dialog: Option<Box<Dialog>>,
+    /// Timestamp of the last message, the active dialog expires after a configured idle time
+    #[serde(default)]
+    last_activity: i64,
+    /// Sent with menus of the current dialog
+    #[serde(default)]
+    menu_id: MenuId,
+    /// Dialogs started by the bot itself expire at this timestamp instead of after the idle time
+    #[serde(default)]
+    expires_at: Option<i64>,
 }
 
-macro_rules! try_find_dialog {
-    ($message:expr, $user_info:expr, $process_message:ident, $DialogType:ident $(, $DialogTypes:ident)*) => {{
-        let dialog_init_result = $DialogType::make($message, $user_info);
-        match dialog_init_result {
-            DialogInitializationResult::NotProcessed => try_find_dialog!($message, $user_info, $process_message $(,$DialogTypes)*),
-            DialogInitializationResult::Finished(reply, event) => { 
-                $process_message(reply, event, None);
-                None
-            }
-            DialogInitializationResult::StartedProcessing(reply, event, dialog) => {
-                $process_message(reply, event, None);
-                Some(dialog)
-            }
+impl DialogsProcessor {
+    pub fn new() -> Self {
+        Self {
+            active_dialog: None,
+            last_activity: 0,
+            menu_id: 0,
+            expires_at: None,
         }
-    }};
+    }
 
-    ($message:expr, $user_info:expr, $process_message:ident) => {None};
-}
+    pub fn is_idle(&self, now: i64) -> bool {
+        self.active_dialog.is_none() ||
+        match self.expires_at {
+            Some(expires_at) => now > expires_at,
+            None => dialog_processing::is_expired(self.last_activity, now),
+        }
+    }
 
-impl DialogsProcessor {
-    pub fn new() -> Self {
-        Self { active_dialog: None }
+    /// Whether buttons of the menu are answers to the active dialog.
+    pub fn is_menu_active(&self, menu_id: MenuId, now: i64) -> bool {
+        !self.is_idle(now) && self.menu_id == menu_id
+    }
+
+    /// Drops the active dialog when a message is handled without it, its menus become stale.
+    fn interrupt(&mut self, now: i64) {
+        self.active_dialog = None;
+        self.expires_at = None;
+        self.last_activity = now;
+        self.menu_id += 1;
+    }
+
+    /// Makes the dialog process the next messages till `expires_at`, e.g. answers to a menu sent by the bot itself.
+    /// Returns id for the menu of the dialog.
+    pub fn start(&mut self, dialog: Box<Dialog>, now: i64, expires_at: i64) -> MenuId {
+        self.active_dialog = Some(dialog);
+        self.expires_at = Some(expires_at);
+        self.last_activity = now;
+        self.menu_id += 1;
+        self.menu_id
     }
 
     pub fn process(&mut self,
                    message: &str,
+                   menu_message: Option<MessageID>,
                    user_info: &mut UserInfo,
                    message_sender: &mut MessageSender,
-                   events_sender: &mut EventsSender) {
+                   events_sender: &mut EventsSender,
+                   outbox: &mut Outbox,
+                   now: i64) {
+        let command = message.split_whitespace().next().unwrap_or("");
+        if let Some(BuiltinCommand::Cancel) = dialog_processing::find_builtin(message) {
+            let reply = if self.is_idle(now) { "Nothing to cancel" } else { "Canceled!" };
+            self.interrupt(now);
+            message_sender.send_text(user_info.chat_id, reply.to_string());
+            return;
+        }
+
+        // a new command or a message after a long pause isn't an answer to the old dialog
+        if self.is_idle(now) || command.starts_with('/') {
+            self.active_dialog = None;
+        }
+        self.last_activity = now;
+        // the message may start a new dialog, menus of the previous one become stale
+        if self.active_dialog.is_none() {
+            self.expires_at = None;
+            self.menu_id += 1;
+        }
+        let menu_id = self.menu_id;
+
         let user_info = RefCell::from(user_info);
         let mut process_action = |reply: Option<ReplyMessage>, event: Option<Event>, channel_message: Option<ChannelMessage>| {
-            if let Some(reply) = reply {
-                match reply.menu {
-                    Some(menu) => {
-                        message_sender.send_menu(user_info.borrow().chat_id, reply.text, menu)
-                    }
-                    None => message_sender.send_text(user_info.borrow().chat_id, reply.text),
-                };
+            let event_result = match event {
+                Some(event) => apply_event(event, user_info.borrow_mut().deref_mut(), events_sender),
+                None => Ok(()),
             };
 
-            if let Some(event) = event {
-                match event {
-                    Event::WholeDay(event) => events_sender.post_whole_day(event),
-                    Event::PartialDay(event) => events_sender.post_partial_day(event)
+            let (reply, channel_message) = match event_result {
+                Ok(_) => (reply, channel_message),
+                Err(EventFailure::Postponed(kind, event, error)) => {
+                    warn!("Couldn't post event, will retry later: {:?}", error);
+                    outbox.push(PendingEvent::new(user_info.borrow().chat_id,
+                                                  kind,
+                                                  user_info.borrow().team.clone(),
+                                                  event,
+                                                  channel_message.map(|message| message.text),
+                                                  now));
+                    (Some(ReplyMessage::new(format!("Postponed: {}. The event will be posted as soon as possible", error),
+                                            None)),
+                     None)
                 }
-            }
+                Err(EventFailure::Failed(error)) => {
+                    error!("Couldn't update calendar: {:?}", error);
+                    (Some(ReplyMessage::new(format!("Failed: {}", error), None)), None)
+                }
+            };
+
+            if let Some(reply) = reply {
+                let chat_id = user_info.borrow().chat_id;
+                match (menu_message, reply.menu) {
+                    (Some(message_id), menu) => message_sender.edit_message(chat_id, message_id, reply.text, menu, menu_id),
+                    (None, Some(menu)) => message_sender.send_menu(chat_id, reply.text, menu, menu_id),
+                    (None, None) => message_sender.send_text(chat_id, reply.text),
+                };
+            };
 
             if let Some(message) = channel_message {
-                message_sender.send_status_to_channel(message.text)
+                let channel = teams::get_status_channel(user_info.borrow().team.as_ref());
+                message_sender.send_status_to_channel(&channel, message.text)
             }
         };
 
@@ -91,24 +180,111 @@ impl DialogsProcessor {
                 }
             }
             None => {
-                try_find_dialog![message,
-                                 user_info.borrow_mut().deref_mut(),
-                                 process_action,
-                                 WfhDialog,
-                                 HelpDialog,
-                                 WhoAmIDialog,
-                                 SetMyNameDialog]
+                let dialog_init_result = dialog_processing::make_dialog(message, user_info.borrow_mut().deref_mut());
+                match dialog_init_result {
+                    DialogInitializationResult::NotProcessed => None,
+                    DialogInitializationResult::Finished(reply, event) => {
+                        process_action(reply, event, None);
+                        None
+                    }
+                    DialogInitializationResult::StartedProcessing(reply, event, dialog) => {
+                        process_action(reply, event, None);
+                        Some(dialog)
+                    }
+                }
             }
         }
     }
 }
 
-// TODO: think how to remove copy-paste of dialog types
+enum EventFailure {
+    /// New event of the given absence kind which can be posted later
+    Postponed(String, CalendarEvent, EventsError),
+    Failed(EventsError),
+}
+
+fn post_event(team: Option<&str>, event: CalendarEvent, events_sender: &mut EventsSender) -> EventsResult<String> {
+    match event {
+        CalendarEvent::WholeDay(event) => events_sender.post_whole_day(team, event),
+        CalendarEvent::PartialDay(event) => events_sender.post_partial_day(team, event),
+    }
+}
+
+fn apply_event(event: Event, user_info: &mut UserInfo, events_sender: &mut EventsSender) -> Result<(), EventFailure> {
+    match event {
+        Event::New(kind, mut event) => {
+            let event_id = make_event_id(user_info.chat_id, &event, Utc::now().timestamp());
+            event.set_id(event_id);
+            let team = user_info.team.clone();
+            match post_event(team.as_ref().map(String::as_str), event.clone(), events_sender) {
+                Ok(event_id) => user_info.add_event(UserEvent::new(event_id, kind, team, event)),
+                Err(error) => {
+                    return Err(if error.is_transient() {
+                                   EventFailure::Postponed(kind, event, error)
+                               } else {
+                                   EventFailure::Failed(error)
+                               })
+                }
+            }
+        }
+        Event::Update(user_event, event) => {
+            let team = user_event.team.as_ref().map(String::as_str);
+            match event.clone() {
+                CalendarEvent::WholeDay(event) => events_sender.update_whole_day(team, &user_event.id, event),
+                CalendarEvent::PartialDay(event) => events_sender.update_partial_day(team, &user_event.id, event),
+            }.map_err(EventFailure::Failed)?;
+            user_info.update_event(&user_event.id, event);
+        }
+        Event::Delete(user_event) => {
+            match events_sender.delete_event(user_event.team.as_ref().map(String::as_str), &user_event.id) {
+                // the event was already deleted from the calendar, e.g. by hand
+                Ok(()) | Err(EventsError::Rejected(404, _)) | Err(EventsError::Rejected(410, _)) => {}
+                Err(error) => return Err(EventFailure::Failed(error)),
+            }
+            user_info.remove_event(&user_event.id);
+        }
+    }
+
+    Ok(())
+}
+
+/// The only list of dialogs, the order defines which one gets a command first.
 pub fn init_dialog_types() {
-    dialog_processing::register_dialog::<WfhDialog>();
+    dialog_processing::register_dialog::<AbsenceDialog>();
+    dialog_processing::register_dialog::<MyEventsDialog>();
     dialog_processing::register_dialog::<HelpDialog>();
     dialog_processing::register_dialog::<WhoAmIDialog>();
     dialog_processing::register_dialog::<SetMyNameDialog>();
+    dialog_processing::register_dialog::<SetTeamDialog>();
+    dialog_processing::register_dialog::<SetTimeZoneDialog>();
+    dialog_processing::register_dialog::<RemindersDialog>();
+    dialog_processing::register_builtin("who-query",
+                                        queries::get_who_commands,
+                                        BuiltinCommand::Query(queries::answer_who_query));
+    dialog_processing::register_builtin("whereis-query",
+                                        queries::get_where_is_commands,
+                                        BuiltinCommand::Query(queries::answer_where_is_query));
+    dialog_processing::register_builtin("cancel", get_cancel_commands, BuiltinCommand::Cancel);
+}
+
+fn get_cancel_commands() -> Vec<CommandDescription> {
+    vec![CommandDescription::new(CANCEL_COMMAND, "Stop the current conversation with the bot").with_example(CANCEL_COMMAND)]
+}
+
+/// Everything the bot understands, depends on the configuration.
+pub fn get_commands() -> Vec<CommandDescription> {
+    dialog_processing::get_commands()
+}
+
+pub fn init_configuration(configuration: &Configuration) {
+    absence::register_absence_kinds(configuration.absence_kinds.clone());
+    teams::register_teams(configuration.working_hours,
+                          configuration.status_channel.clone(),
+                          configuration.teams.clone());
+    digest::register_digest_hour(configuration.digest_hour);
+    queries::register_query_days(configuration.query_days);
+    reminders::register_reminder_hour(configuration.reminder_hour);
+    dialog_processing::register_dialog_timeout(configuration.dialog_timeout_minutes);
 }
 
 #[derive(Serialize, Deserialize)]
@@ -131,6 +307,12 @@ pub struct UserCollection<'a> {
     data_saver: &'a mut DataSaver,
     users: HashMap<ChatID, UserState>,
     last_message_id: Option<i64>,
+    outbox: Outbox,
+    last_digest: Option<NaiveDate>,
+}
+
+fn is_not_found(error: &(Error + 'static)) -> bool {
+    error.downcast_ref::<io::Error>().map_or(false, |error| error.kind() == io::ErrorKind::NotFound)
 }
 
 impl<'a> UserCollection<'a> {
@@ -142,6 +324,8 @@ impl<'a> UserCollection<'a> {
             data_saver,
             users: HashMap::<ChatID, UserState>::new(),
             last_message_id: None,
+            outbox: Outbox::new(),
+            last_digest: None,
         };
 
         result.load();
@@ -150,6 +334,11 @@ impl<'a> UserCollection<'a> {
     }
 
     fn save(&self) {
+        let last_message_id = match self.last_message_id {
+            Some(id) => id,
+            // nothing to save before the first message
+            None => return,
+        };
         let users: Vec<_> = self.users
             .iter()
             .map(|(id, user)| {
@@ -157,8 +346,7 @@ impl<'a> UserCollection<'a> {
                  })
             .collect();
         let serialization_data =
-            UserCollectionSerializationData::new(
-                self.last_message_id.expect("last_message_id not specified but save is invoked"), users);
+            UserCollectionSerializationData::new(last_message_id, users, self.outbox.clone(), self.last_digest);
         match self.data_saver.save_data(serialization_data) {
             Ok(_) => {}
             Err(error) => error!("Couldn't save bot state: {}", error.description()),
@@ -173,19 +361,111 @@ impl<'a> UserCollection<'a> {
                     self.users
                         .insert(user_info.chat_id, UserState::new(user_info.info, Some(user_info.processor)));
                 }
+                self.outbox = user_data.outbox;
+                self.outbox.deduplicate();
+                self.last_digest = user_data.last_digest;
             }
+            // a missing file is a fresh start, other files mustn't be overwritten
+            Err(ref error) if is_not_found(error.as_ref()) => self.last_message_id = Some(0),
             Err(error) => warn!("Couldn't load bot state: {:?}", error.description()),
         }
     }
-}
 
-impl<'a> MessageProcessor for UserCollection<'a> {
-    fn process_message(&mut self,
-                       message_sender: &mut MessageSender,
-                       chat_id: ChatID,
-                       first_name: &str,
-                       last_name: Option<&str>,
-                       message: &str) {
+    fn process_digest(&mut self, message_sender: &mut MessageSender, now: NaiveDateTime) {
+        let today = now.date();
+        if !digest::is_digest_time(&now) || self.last_digest == Some(today) {
+            return;
+        }
+
+        self.last_digest = Some(today);
+        for (channel, text) in digest::make_digests(self.users.values().map(|user| &user.user_info), &today) {
+            message_sender.send_status_to_channel(&channel, text);
+        }
+        self.save();
+    }
+
+    fn process_reminders(&mut self, message_sender: &mut MessageSender, utc_now: DateTime<Utc>) {
+        let mut is_changed = false;
+        for (chat_id, user) in self.users.iter_mut() {
+            let now = user.user_info.get_time_zone().from_utc(&utc_now).naive_local();
+            if !user.dialogs_processor.is_idle(utc_now.timestamp()) ||
+               !reminders::is_reminder_due(&user.user_info, &now) {
+                continue;
+            }
+            let (dialog, kind_name) = match absence::make_when_dialog() {
+                Some(result) => result,
+                None => return,
+            };
+
+            // the reminder may be answered till the end of the user's day
+            let zone = user.user_info.get_time_zone();
+            let day_end = zone.from_local_date(&(now.date() + ::time::Duration::days(1))).and_hms(0, 0, 0);
+            let menu_id = user.dialogs_processor.start(Box::new(dialog), utc_now.timestamp(), day_end.timestamp());
+            user.user_info.reminders.last_reminder = Some(now.date());
+            message_sender.send_menu(*chat_id,
+                                     reminders::make_reminder_text(&kind_name),
+                                     WHEN_MENU.clone(),
+                                     menu_id);
+            is_changed = true;
+        }
+
+        if is_changed {
+            self.save();
+        }
+    }
+
+    fn process_outbox(&mut self, message_sender: &mut MessageSender, now: i64) {
+        if self.outbox.is_empty() {
+            return;
+        }
+
+        let due_events = self.outbox.take_due(now);
+        if due_events.is_empty() {
+            return;
+        }
+
+        for pending in due_events {
+            let description = format!("{} {}", pending.kind, absence::describe_event(&pending.event));
+            match post_event(pending.team.as_ref().map(String::as_str), pending.event.clone(), self.events_sender) {
+                Ok(event_id) => {
+                    if let Some(user) = self.users.get_mut(&pending.chat_id) {
+                        user.user_info.add_event(UserEvent::new(event_id,
+                                                                pending.kind.clone(),
+                                                                pending.team.clone(),
+                                                                pending.event.clone()));
+                    }
+                    message_sender.send_text(pending.chat_id, format!("Posted postponed {}", description));
+                    if let Some(text) = pending.channel_message {
+                        message_sender.send_status_to_channel(&teams::get_status_channel(pending.team.as_ref()),
+                                                              text);
+                    }
+                }
+                Err(error) => {
+                    warn!("Retry of postponed event failed: {:?}", error);
+                    let chat_id = pending.chat_id;
+                    let failed = if error.is_transient() {
+                        self.outbox.retry_later(pending, now)
+                    } else {
+                        Some(pending)
+                    };
+                    if failed.is_some() {
+                        message_sender.send_text(chat_id, format!("Failed to post {}: {}", description, error));
+                    }
+                }
+            }
+        }
+
+        self.save();
+    }
+
+    /// `menu_message` is the message with the pressed menu button, replies replace it.
+    fn process_text(&mut self,
+                    message_sender: &mut MessageSender,
+                    chat_id: ChatID,
+                    first_name: &str,
+                    last_name: Option<&str>,
+                    message: &str,
+                    menu_message: Option<MessageID>) {
         let get_name = || {
             (first_name.to_string(),
              match last_name {
@@ -194,6 +474,7 @@ impl<'a> MessageProcessor for UserCollection<'a> {
              })
         };
 
+        let now = Utc::now().timestamp();
         {
             let user = self.users
                 .entry(chat_id)
@@ -203,12 +484,81 @@ impl<'a> MessageProcessor for UserCollection<'a> {
                                                                  first_name,
                                                                  last_name), None)
                                 });
-            user.dialogs_processor
-                .process(message, &mut user.user_info, message_sender, self.events_sender);
+            user.user_info.last_active = Some(user.user_info.get_time_zone().today().naive_local());
+        }
+
+        match dialog_processing::find_builtin(message) {
+            Some(BuiltinCommand::Query(answer_query)) => {
+                let answer = {
+                    let user_info = &self.users[&chat_id].user_info;
+                    let users: Vec<&UserInfo> = self.users.values().map(|user| &user.user_info).collect();
+                    answer_query(message,
+                                 user_info.team.as_ref(),
+                                 &users,
+                                 &user_info.get_time_zone().today().naive_local())
+                };
+                // like any other command, a query isn't an answer to the active dialog
+                if let Some(user) = self.users.get_mut(&chat_id) {
+                    user.dialogs_processor.interrupt(now);
+                }
+                message_sender.send_text(chat_id, answer);
+            }
+            _ => {
+                if let Some(user) = self.users.get_mut(&chat_id) {
+                    user.dialogs_processor.process(message,
+                                                   menu_message,
+                                                   &mut user.user_info,
+                                                   message_sender,
+                                                   self.events_sender,
+                                                   &mut self.outbox,
+                                                   now);
+                }
+            }
         }
 
         self.save();
     }
+}
+
+impl<'a> MessageProcessor for UserCollection<'a> {
+    fn process_update(&mut self, message_sender: &mut MessageSender, update: IncomingUpdate) -> Result<(), String> {
+        let IncomingUpdate { chat_id, sender, kind } = update;
+        let (text, menu_message) = match kind {
+            IncomingKind::Text(text) => (text, None),
+            IncomingKind::Command { command, arguments } => {
+                if arguments.is_empty() {
+                    (command, None)
+                } else {
+                    (format!("{} {}", command, arguments), None)
+                }
+            }
+            IncomingKind::ButtonPress { message_id, menu_id, key } => {
+                let now = Utc::now().timestamp();
+                let is_active = match (self.users.get(&chat_id), menu_id) {
+                    (Some(user), Some(menu_id)) => user.dialogs_processor.is_menu_active(menu_id, now),
+                    _ => false,
+                };
+                if !is_active {
+                    return Err("This menu is outdated, please send the command again".to_string());
+                }
+                (key, Some(message_id))
+            }
+        };
+
+        self.process_text(message_sender,
+                          chat_id,
+                          &sender.first_name,
+                          sender.last_name.as_ref().map(String::as_str),
+                          &text,
+                          menu_message);
+        Ok(())
+    }
+
+    fn process_timer(&mut self, message_sender: &mut MessageSender) {
+        self.process_outbox(message_sender, Utc::now().timestamp());
+        self.process_digest(message_sender, chrono::Local::now().naive_local());
+        self.process_reminders(message_sender, Utc::now());
+    }
 
     fn is_new_message(&mut self, message_id: i64) -> bool {
         const MAX_DIFFERENCE: i64 = 1000;
@@ -234,31 +584,81 @@ impl<'a> MessageProcessor for UserCollection<'a> {
 mod tests {
 
     use basic_structures::*;
-    use user_data::*;
     use message_processor::*;
     use save_load_state::{DataSaver, SaveResult, LoadResult, UserCollectionSerializationData,
                           UserSerializationInfo};
 
+    use configuration::{Configuration, Team, WorkingHours};
+
+    use chrono::{Datelike, TimeZone, Weekday};
+    use serde_json;
+
     use std::cell::Cell;
 
+    #[derive(Debug)]
     enum Event {
-        WfhOneDay(WfhSingleDay),
+        WholeDay(WholeDayEvent),
+        PartialDay(PartialDayEvent),
+        Updated(String, CalendarEvent),
+        Deleted(String),
     }
 
     struct MockEventsSender {
         events: Vec<Event>,
+        /// Errors returned by the next requests
+        failures: Vec<EventsError>,
+        /// Errors returned by the next deletions
+        delete_failures: Vec<EventsError>,
     }
 
     impl MockEventsSender {
         fn new() -> Self {
-            Self { events: Vec::<Event>::new() }
+            Self::failing(Vec::new())
+        }
+
+        fn failing(failures: Vec<EventsError>) -> Self {
+            Self {
+                events: Vec::<Event>::new(),
+                failures,
+                delete_failures: Vec::new(),
+            }
+        }
+
+        fn push(&mut self, event: Event) -> EventsResult<String> {
+            if !self.failures.is_empty() {
+                return Err(self.failures.remove(0));
+            }
+
+            let id = format!("event{}", self.events.len());
+            self.events.push(event);
+            Ok(id)
         }
     }
 
     impl EventsSender for MockEventsSender {
-        fn post_whole_day(&mut self, event: WfhSingleDay) {
-            self.events
-                .push(Event::WfhOneDay(event));
+        fn post_whole_day(&mut self, _: Option<&str>, event: WholeDayEvent) -> EventsResult<String> {
+            self.push(Event::WholeDay(event))
+        }
+
+        fn post_partial_day(&mut self, _: Option<&str>, event: PartialDayEvent) -> EventsResult<String> {
+            self.push(Event::PartialDay(event))
+        }
+
+        fn update_whole_day(&mut self, _: Option<&str>, event_id: &str, event: WholeDayEvent) -> EventsResult<()> {
+            self.push(Event::Updated(event_id.to_string(), CalendarEvent::WholeDay(event)))
+                .map(|_| ())
+        }
+
+        fn update_partial_day(&mut self, _: Option<&str>, event_id: &str, event: PartialDayEvent) -> EventsResult<()> {
+            self.push(Event::Updated(event_id.to_string(), CalendarEvent::PartialDay(event)))
+                .map(|_| ())
+        }
+
+        fn delete_event(&mut self, _: Option<&str>, event_id: &str) -> EventsResult<()> {
+            if !self.delete_failures.is_empty() {
+                return Err(self.delete_failures.remove(0));
+            }
+            self.push(Event::Deleted(event_id.to_string())).map(|_| ())
         }
     }
 
@@ -283,11 +683,23 @@ mod tests {
 
     struct MockMessageSender {
         messages: Vec<Message>,
+        channel_messages: Vec<String>,
+        /// Channel of each channel message
+        channels: Vec<String>,
+        menu_ids: Vec<MenuId>,
+        /// Edited message and its new content
+        edits: Vec<(MessageID, Message)>,
     }
 
     impl MockMessageSender {
         fn new() -> Self {
-            Self { messages: Vec::<Message>::new() }
+            Self {
+                messages: Vec::<Message>::new(),
+                channel_messages: Vec::<String>::new(),
+                channels: Vec::<String>::new(),
+                menu_ids: Vec::<MenuId>::new(),
+                edits: Vec::new(),
+            }
         }
     }
 
@@ -296,9 +708,20 @@ mod tests {
             self.messages.push(Message::new(chat_id, text, None));
         }
 
-        fn send_menu(&mut self, chat_id: ChatID, text: String, menu: Menu) {
+        fn send_menu(&mut self, chat_id: ChatID, text: String, menu: Menu, menu_id: MenuId) {
             self.messages
                 .push(Message::new(chat_id, text, Some(menu)));
+            self.menu_ids.push(menu_id);
+        }
+
+        fn edit_message(&mut self, chat_id: ChatID, message_id: MessageID, text: String, menu: Option<Menu>, menu_id: MenuId) {
+            self.edits.push((message_id, Message::new(chat_id, text, menu)));
+            self.menu_ids.push(menu_id);
+        }
+
+        fn send_status_to_channel(&mut self, channel: &str, text: String) {
+            self.channels.push(channel.to_string());
+            self.channel_messages.push(text);
         }
     }
 
@@ -324,56 +747,296 @@ mod tests {
 
         fn load_data(&self) -> LoadResult {
             self.load_count.set(self.load_count.get() + 1);
-            Ok(UserCollectionSerializationData::new(42, Vec::<UserSerializationInfo>::new()))
+            Ok(UserCollectionSerializationData::new(42, Vec::<UserSerializationInfo>::new(), Outbox::new(), None))
         }
     }
 
-    fn simple_reply_test(first_name: &str,
-                         last_name: Option<&str>,
-                         text: &str,
-                         expected_reply: &str) {
-        let mut message_sender = MockMessageSender::new();
-        let mut events_sender = MockEventsSender::new();
-        let mut data_saver = MockDataSaver::new();
-        {
-            let mut message_processor =
-                UserCollection::new(&mut message_sender, &mut events_sender, &mut data_saver);
+    fn chat_id() -> ChatID {
+        ChatID::new(42)
+    }
 
-            message_processor.process_message(42, first_name, last_name, text);
-        }
-        assert_eq!(events_sender.events.len(), 0);
-        assert_eq!(data_saver.save_count, Cell::new(1));
-        assert_eq!(message_sender.messages,
-                   vec![Message::new(42, expected_reply, None)]);
+    fn configuration() -> Configuration {
+        let mut configuration = Configuration::default();
+        configuration.teams.push(Team {
+                                     name: "support".to_string(),
+                                     calendar_id: None,
+                                     key_file: None,
+                                     calendar_backend: None,
+                                     working_hours: Some(WorkingHours { start: 12, end: 22 }),
+                                     status_channel: Some("-1001234567890".to_string()),
+                                 });
+        configuration.status_channel = "@BigBrotherEvents".to_string();
+        configuration.digest_hour = Some(9);
+        configuration.reminder_hour = Some(10);
+        configuration
     }
 
     macro_rules! define_send_reply_test {
     ($first_name:expr, $last_name:expr, $([$request:expr, $reply:expr]),+) => (
+        init_dialog_types();
+        init_configuration(&configuration());
+
         let mut message_sender = MockMessageSender::new();
         let mut events_sender = MockEventsSender::new();
         let mut data_saver = MockDataSaver::new();
         {
             let mut message_processor = super::UserCollection::new(
-                &mut message_sender,
                 &mut events_sender,
                 &mut data_saver);
             
-            $(message_processor.process_message(42, $first_name, $last_name, $request);)*
+            $(message_processor.process_update(&mut message_sender, text_update(chat_id(), $first_name, $last_name, $request)).unwrap();)*
         }
 
         assert_eq!(events_sender.events.len(), 0);
 
-        let messages = vec![$(Message::new(42, $reply, None), )*];
+        let messages = vec![$(Message::new(chat_id(), $reply, None), )*];
         assert_eq!(data_saver.save_count, Cell::new(messages.len() as i32));
         assert_eq!(message_sender.messages, messages);
     )
 }
 
+    macro_rules! define_menu_dialog_test {
+    ($first_name:expr, $last_name:expr, $([$request:expr, $reply:expr, $menu:expr]),+) => {{
+        init_dialog_types();
+        init_configuration(&configuration());
+
+        let mut message_sender = MockMessageSender::new();
+        let mut events_sender = MockEventsSender::new();
+        let mut data_saver = MockDataSaver::new();
+        {
+            let mut message_processor = super::UserCollection::new(
+                &mut events_sender,
+                &mut data_saver);
+            
+            $(message_processor.process_update(&mut message_sender, text_update(chat_id(), $first_name, $last_name, $request)).unwrap();)*
+        }
+
+        let messages = vec![$(Message::new(chat_id(), $reply, $menu), )*];
+        assert_eq!(message_sender.messages, messages);
+
+        (events_sender.events, message_sender.channel_messages)
+    }}
+}
+
+    fn yes_no_menu() -> Option<Menu> {
+        Some(vec![vec!["yes".to_string(), "no".to_string()]])
+    }
+
+    fn when_menu() -> Option<Menu> {
+        Some(vec![vec!["today".to_string(), "tomorrow".to_string()],
+                  vec!["untill now".to_string(), "from now".to_string()],
+                  vec!["morning".to_string(), "afternoon".to_string()],
+                  vec!["hours".to_string(), "other".to_string()]])
+    }
+
     #[test]
     fn test_help() {
         define_send_reply_test!("Vasiliy",
                                 None,
-                                ["/help", "https://www.youtube.com/watch?v=yWP6Qki8mWc"]);
+                                ["/help wfh",
+                                 "/wfh – Report wfh\nExamples:\n/wfh\n/wfh tomorrow\n/wfh 24.12-28.12\n\
+                                  /wfh today after 14:00\n/wfh every friday"],
+                                ["/help /setteam", "/setteam – Choose your team\nExamples:\n/setteam support"],
+                                ["/help /who",
+                                 "/who – Show who is away from your team\nExamples:\n/who\n/who tomorrow\n/who 24.12-28.12"]);
+
+        let overview = "Available commands:\n\
+                        /wfh – Report wfh\n\
+                        /vacation – Report vacation\n\
+                        /sick – Report sick day\n\
+                        /trip – Report business trip\n\
+                        /dayoff – Report day off\n\
+                        /myevents – Show your upcoming events to delete or move them\n\
+                        /help – Show the available commands or details of one of them\n\
+                        /whoami – Show your name in the calendar\n\
+                        /setmyname – Change your name in the calendar\n\
+                        /setteam – Choose your team\n\
+                        /settz – Set your time zone\n\
+                        /reminders – Turn reminders on or off or change their hour\n\
+                        /who – Show who is away from your team\n\
+                        /whereis – Show absences of a colleague\n\
+                        /cancel – Stop the current conversation with the bot\n\
+                        Send \"/help <command>\" for details";
+        define_send_reply_test!("Vasiliy",
+                                None,
+                                ["/help", overview],
+                                ["/help nothing", format!("Unknown command \"nothing\"\n\n{}", overview)]);
+    }
+
+    #[test]
+    fn test_dialog_registry() {
+        init_dialog_types();
+        init_configuration(&configuration());
+
+        let mut message_sender = MockMessageSender::new();
+        let mut events_sender = MockEventsSender::new();
+        let mut outbox = Outbox::new();
+        let mut user_info = UserInfo::new(chat_id(), "Vasiliy".to_string(), "Pupkin".to_string());
+        let mut dialogs_processor = DialogsProcessor::new();
+        for message in &["/helpme", "/wfh"] {
+            dialogs_processor.process(message,
+                                      None,
+                                      &mut user_info,
+                                      &mut message_sender,
+                                      &mut events_sender,
+                                      &mut outbox,
+                                      0);
+        }
+
+        // active dialog is restored by its registered name
+        let saved = serde_json::to_string(&dialogs_processor).unwrap();
+        assert!(saved.contains("wfh-dialog"));
+        let mut dialogs_processor: DialogsProcessor = serde_json::from_str(&saved).unwrap();
+        dialogs_processor.process("tomorrow",
+                                  None,
+                                  &mut user_info,
+                                  &mut message_sender,
+                                  &mut events_sender,
+                                  &mut outbox,
+                                  0);
+
+        assert_eq!(message_sender.messages,
+                   vec![Message::new(chat_id(), "When?", when_menu()),
+                        Message::new(chat_id(), "Confirm event wfh for tomorrow?", yes_no_menu())]);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let (events, _) =
+            define_menu_dialog_test!("Vasiliy",
+                                     Some("Pupkin"),
+                                     ["/wfh", "When?", when_menu()],
+                                     ["/cancel", "Canceled!", None],
+                                     ["/cancel", "Nothing to cancel", None],
+                                     ["/wfh", "When?", when_menu()],
+                                     ["/whoami", "Vasiliy Pupkin\nIn calendar will be \"V.Pupkin\"", None]);
+
+        assert_eq!(events.len(), 0);
+    }
+
+    #[test]
+    fn test_stale_callback() {
+        init_dialog_types();
+        init_configuration(&configuration());
+
+        let mut message_sender = MockMessageSender::new();
+        let mut events_sender = MockEventsSender::new();
+        let mut data_saver = MockDataSaver::new();
+        let mut results = Vec::new();
+        {
+            let mut message_processor = super::UserCollection::new(&mut events_sender,
+                                                                   &mut data_saver);
+            for text in &["/wfh", "/cancel", "/wfh"] {
+                send_message(&mut message_processor, &mut message_sender, 42, "Vasiliy", "Pupkin", text);
+            }
+            let (old_menu, new_menu) = (message_sender.menu_ids[0], message_sender.menu_ids[1]);
+            assert!(old_menu != new_menu);
+
+            for &menu_id in &[Some(old_menu), None, Some(new_menu)] {
+                let update = IncomingUpdate::button_press(chat_id(),
+                                                          SenderIdentity::new("Vasiliy", Some("Pupkin")),
+                                                          MessageID::new(7),
+                                                          menu_id,
+                                                          "tomorrow");
+                results.push(message_processor.process_update(&mut message_sender, update));
+            }
+        }
+
+        let outdated = Err("This menu is outdated, please send the command again".to_string());
+        assert_eq!(results, vec![outdated.clone(), outdated, Ok(())]);
+        assert_eq!(message_sender.edits,
+                   vec![(MessageID::new(7), Message::new(chat_id(), "Confirm event wfh for tomorrow?", yes_no_menu()))]);
+    }
+
+    #[test]
+    fn test_query_interrupts_dialog() {
+        init_dialog_types();
+        init_configuration(&configuration());
+
+        let mut message_sender = MockMessageSender::new();
+        let mut events_sender = MockEventsSender::new();
+        let mut data_saver = MockDataSaver::new();
+        let result;
+        {
+            let mut message_processor = super::UserCollection::new(&mut events_sender,
+                                                                   &mut data_saver);
+            for text in &["/wfh", "/who 01.01.2031", "today"] {
+                send_message(&mut message_processor, &mut message_sender, 42, "Vasiliy", "Pupkin", text);
+            }
+            let update = IncomingUpdate::button_press(chat_id(),
+                                                      SenderIdentity::new("Vasiliy", Some("Pupkin")),
+                                                      MessageID::new(7),
+                                                      Some(message_sender.menu_ids[0]),
+                                                      "today");
+            result = message_processor.process_update(&mut message_sender, update);
+        }
+
+        assert_eq!(result, Err("This menu is outdated, please send the command again".to_string()));
+        assert_eq!(message_sender.messages,
+                   vec![Message::new(chat_id(), "When?", when_menu()),
+                        Message::new(chat_id(), "Nobody is away on 01.01.2031", None)]);
+        assert_eq!(data_saver.save_count.get(), 3);
+    }
+
+    #[test]
+    fn test_edit_menu() {
+        init_dialog_types();
+        init_configuration(&configuration());
+
+        let mut message_sender = MockMessageSender::new();
+        let mut events_sender = MockEventsSender::new();
+        let mut data_saver = MockDataSaver::new();
+        let menu_message = MessageID::new(7);
+        {
+            let mut message_processor = super::UserCollection::new(&mut events_sender,
+                                                                   &mut data_saver);
+            send_message(&mut message_processor, &mut message_sender, 42, "Vasiliy", "Pupkin", "/wfh");
+            for data in &["today", "yes"] {
+                let menu_id = *message_sender.menu_ids.last().unwrap();
+                let update = IncomingUpdate::button_press(chat_id(),
+                                                          SenderIdentity::new("Vasiliy", Some("Pupkin")),
+                                                          menu_message,
+                                                          Some(menu_id),
+                                                          data);
+                message_processor.process_update(&mut message_sender, update).unwrap();
+            }
+        }
+
+        assert_eq!(events_sender.events.len(), 1);
+        assert_eq!(message_sender.messages, vec![Message::new(chat_id(), "When?", when_menu())]);
+        assert_eq!(message_sender.edits,
+                   vec![(menu_message, Message::new(chat_id(), "Confirm event wfh for today?", yes_no_menu())),
+                        (menu_message, Message::new(chat_id(), "Applied: wfh for today", None))]);
+    }
+
+    #[test]
+    fn test_dialog_timeout() {
+        init_dialog_types();
+        init_configuration(&configuration());
+
+        let mut message_sender = MockMessageSender::new();
+        let mut events_sender = MockEventsSender::new();
+        let mut outbox = Outbox::new();
+        let mut user_info = UserInfo::new(chat_id(), "Vasiliy".to_string(), "Pupkin".to_string());
+        let mut dialogs_processor = DialogsProcessor::new();
+        let minute = 60;
+        for &(message, now) in &[("/wfh", 0), ("/cancel", 60 * minute + 1), ("/wfh", 0), ("today", 60 * minute)] {
+            dialogs_processor.process(message,
+                                      None,
+                                      &mut user_info,
+                                      &mut message_sender,
+                                      &mut events_sender,
+                                      &mut outbox,
+                                      now);
+        }
+
+        assert!(!dialogs_processor.is_idle(120 * minute));
+        assert!(dialogs_processor.is_idle(120 * minute + 1));
+        assert_eq!(message_sender.messages,
+                   vec![Message::new(chat_id(), "When?", when_menu()),
+                        Message::new(chat_id(), "Nothing to cancel", None),
+                        Message::new(chat_id(), "When?", when_menu()),
+                        Message::new(chat_id(), "Confirm event wfh for today?", yes_no_menu())]);
     }
 
     #[test]
@@ -398,4 +1061,725 @@ mod tests {
                                 ["/whoami", "Vasiliy \nIn calendar will be \"A.Crowley\""]);
     }
 
+    #[test]
+    fn test_wfh_from_now() {
+        let (events, channel_messages) =
+            define_menu_dialog_test!("Vasiliy",
+                                     Some("Pupkin"),
+                                     ["/wfh", "When?", when_menu()],
+                                     ["from now", "Confirm event wfh for today from now?", yes_no_menu()],
+                                     ["yes", "Applied: wfh today from now", None]);
+
+        assert_eq!(events.len(), 1);
+        match events[0] {
+            Event::PartialDay(ref event) => {
+                assert!(serde_json::to_string(event).unwrap().contains("\"summary\":\"WFH: V.Pupkin\""))
+            }
+            ref other => panic!("Unexpected event {:?}", other),
+        }
+        assert_eq!(channel_messages, vec!["wfh today from now".to_string()]);
+    }
+
+    #[test]
+    fn test_wfh_other_dates() {
+        let (events, channel_messages) =
+            define_menu_dialog_test!("Vasiliy",
+                                     Some("Pupkin"),
+                                     ["/wfh", "When?", when_menu()],
+                                     ["other",
+                                      "Which dates? Send a single date like \"24.12\" or a range like \"24.12-28.12\"",
+                                      None],
+                                     ["24.12.2030-28.12.2030",
+                                      "Confirm event wfh from 24.12.2030 to 28.12.2030?",
+                                      yes_no_menu()],
+                                     ["yes", "Applied: wfh from 24.12.2030 to 28.12.2030", None]);
+
+        assert_eq!(events.len(), 1);
+        match events[0] {
+            Event::WholeDay(ref event) => {
+                let json = serde_json::to_string(event).unwrap();
+                assert!(json.contains("\"start\":{\"date\":\"2030-12-24\"}"));
+                assert!(json.contains("\"end\":{\"date\":\"2030-12-28\"}"));
+            }
+            ref other => panic!("Unexpected event {:?}", other),
+        }
+        assert_eq!(channel_messages,
+                   vec!["wfh from 24.12.2030 to 28.12.2030".to_string()]);
+    }
+
+    #[test]
+    fn test_wfh_other_invalid_dates() {
+        let (events, _) =
+            define_menu_dialog_test!("Vasiliy",
+                                     Some("Pupkin"),
+                                     ["/wfh", "When?", when_menu()],
+                                     ["other",
+                                      "Which dates? Send a single date like \"24.12\" or a range like \"24.12-28.12\"",
+                                      None],
+                                     ["28.12.2030-24.12.2030",
+                                      "Couldn't recognize dates in \"28.12.2030-24.12.2030\". Canceled!",
+                                      None]);
+
+        assert_eq!(events.len(), 0);
+    }
+
+    #[test]
+    fn test_wfh_with_arguments() {
+        let (events, channel_messages) =
+            define_menu_dialog_test!("Vasiliy",
+                                     Some("Pupkin"),
+                                     ["/wfh 24.12.2030 after 14:00",
+                                      "Confirm event wfh on 24.12.2030 from 14:00 to 20:00?",
+                                      yes_no_menu()],
+                                     ["yes", "Applied: wfh on 24.12.2030 from 14:00 to 20:00", None]);
+
+        assert_eq!(events.len(), 1);
+        match events[0] {
+            Event::PartialDay(ref event) => {
+                assert!(serde_json::to_string(event).unwrap().contains("2030-12-24T14:00:00"))
+            }
+            ref other => panic!("Unexpected event {:?}", other),
+        }
+        assert_eq!(channel_messages,
+                   vec!["wfh on 24.12.2030 from 14:00 to 20:00".to_string()]);
+    }
+
+    #[test]
+    fn test_team_working_hours() {
+        let (events, channel_messages) =
+            define_menu_dialog_test!("Vasiliy",
+                                     Some("Pupkin"),
+                                     ["/setteam", "Please specify one of the teams: support", None],
+                                     ["/setteam support", "Your team is \"support\"", None],
+                                     ["/wfh 24.12.2030 after 14:00",
+                                      "Confirm event wfh on 24.12.2030 from 14:00 to 22:00?",
+                                      yes_no_menu()],
+                                     ["yes", "Applied: wfh on 24.12.2030 from 14:00 to 22:00", None]);
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(channel_messages,
+                   vec!["wfh on 24.12.2030 from 14:00 to 22:00".to_string()]);
+    }
+
+    #[test]
+    fn test_team_channels() {
+        init_dialog_types();
+        init_configuration(&configuration());
+
+        let mut message_sender = MockMessageSender::new();
+        let mut events_sender = MockEventsSender::new();
+        let mut data_saver = MockDataSaver::new();
+        {
+            let mut message_processor = super::UserCollection::new(&mut events_sender,
+                                                                   &mut data_saver);
+            for text in &["/wfh 24.12.2030", "yes", "/setteam support", "/wfh 25.12.2030", "yes"] {
+                message_processor.process_update(&mut message_sender, text_update(chat_id(), "Vasiliy", Some("Pupkin"), text)).unwrap();
+            }
+        }
+
+        assert_eq!(message_sender.channel_messages,
+                   vec!["wfh for 24.12.2030".to_string(), "wfh for 25.12.2030".to_string()]);
+        // users without a team post to the default channel
+        assert_eq!(message_sender.channels,
+                   vec!["@BigBrotherEvents".to_string(), "-1001234567890".to_string()]);
+    }
+
+    fn today_string() -> String {
+        chrono::Local::today().format("%d.%m.%Y").to_string()
+    }
+
+    fn time_menu(rows: &[&[&str]]) -> Option<Menu> {
+        Some(rows.iter().map(|row| row.iter().map(|time| time.to_string()).collect()).collect())
+    }
+
+    #[test]
+    fn test_wfh_half_day() {
+        let (events, channel_messages) =
+            define_menu_dialog_test!("Vasiliy",
+                                     Some("Pupkin"),
+                                     ["/wfh", "When?", when_menu()],
+                                     ["afternoon",
+                                      format!("Confirm event wfh on {} from 14:30 to 20:00?", today_string()),
+                                      yes_no_menu()],
+                                     ["yes", format!("Applied: wfh on {} from 14:30 to 20:00", today_string()), None],
+                                     ["/wfh tomorrow morning",
+                                      format!("Confirm event wfh on {} from 09:00 to 14:30?",
+                                              (chrono::Local::today() + time::Duration::days(1)).format("%d.%m.%Y")),
+                                      yes_no_menu()]);
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(channel_messages,
+                   vec![format!("wfh on {} from 14:30 to 20:00", today_string())]);
+    }
+
+    #[test]
+    fn test_wfh_hours() {
+        let end_time_menu = time_menu(&[&["15:00", "16:00", "17:00", "18:00"], &["19:00", "20:00"]]);
+        let (events, _) =
+            define_menu_dialog_test!("Vasiliy",
+                                     Some("Pupkin"),
+                                     ["/wfh", "When?", when_menu()],
+                                     ["hours",
+                                      "From?",
+                                      time_menu(&[&["09:00", "10:00", "11:00", "12:00"],
+                                                  &["13:00", "14:00", "15:00", "16:00"],
+                                                  &["17:00", "18:00", "19:00"]])],
+                                     ["14:00", "Till?", end_time_menu.clone()],
+                                     ["13:00", "End should be after 14:00. Till?", end_time_menu.clone()],
+                                     ["16:30",
+                                      format!("Confirm event wfh on {} from 14:00 to 16:30?", today_string()),
+                                      yes_no_menu()],
+                                     ["yes", format!("Applied: wfh on {} from 14:00 to 16:30", today_string()), None],
+                                     ["/wfh", "When?", when_menu()],
+                                     ["hours", "From?", time_menu(&[&["09:00", "10:00", "11:00", "12:00"],
+                                                                    &["13:00", "14:00", "15:00", "16:00"],
+                                                                    &["17:00", "18:00", "19:00"]])],
+                                     ["noon", "Couldn't recognize time \"noon\". Canceled!", None]);
+
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn test_wfh_late_hours() {
+        let start_time_menu = time_menu(&[&["09:00", "10:00", "11:00", "12:00"],
+                                          &["13:00", "14:00", "15:00", "16:00"],
+                                          &["17:00", "18:00", "19:00"]]);
+        let (events, _) =
+            define_menu_dialog_test!("Vasiliy",
+                                     Some("Pupkin"),
+                                     ["/wfh", "When?", when_menu()],
+                                     ["hours", "From?", start_time_menu.clone()],
+                                     ["23:30", "No end time is left after 23:30. From?", start_time_menu.clone()],
+                                     ["22:00", "Till?", time_menu(&[&["23:00"]])]);
+
+        assert_eq!(events.len(), 0);
+    }
+
+    #[test]
+    fn test_incoming_update() {
+        let sender = SenderIdentity::new("Vasiliy", None);
+        assert_eq!(IncomingUpdate::from_text(chat_id(), sender.clone(), "/wfh  24.12").kind,
+                   IncomingKind::Command {
+                       command: "/wfh".to_string(),
+                       arguments: "24.12".to_string(),
+                   });
+        assert_eq!(IncomingUpdate::from_text(chat_id(), sender.clone(), "/help").kind,
+                   IncomingKind::Command {
+                       command: "/help".to_string(),
+                       arguments: "".to_string(),
+                   });
+        assert_eq!(IncomingUpdate::from_text(chat_id(), sender, "tomorrow").kind,
+                   IncomingKind::Text("tomorrow".to_string()));
+
+        // Data files written with telegram-bot ids stay readable
+        assert_eq!(serde_json::to_string(&ChatID::new(-42)).unwrap(), "-42");
+        assert_eq!(serde_json::from_str::<ChatID>("42").unwrap(), chat_id());
+    }
+
+    #[test]
+    fn test_time_zone() {
+        let (events, _) =
+            define_menu_dialog_test!("Vasiliy",
+                                     Some("Pupkin"),
+                                     ["/settz Mars/Olympus",
+                                      "No valid time zone is specified. Please specify it in format \"/settz Europe/Berlin\"",
+                                      None],
+                                     ["/settz Europe/Berlin", "Your time zone is Europe/Berlin", None],
+                                     ["/wfh 01.07.2031 from 10-12",
+                                      "Confirm event wfh on 01.07.2031 from 10:00 to 12:00?",
+                                      yes_no_menu()],
+                                     ["yes", "Applied: wfh on 01.07.2031 from 10:00 to 12:00", None],
+                                     ["/wfh 24.12.2030", "Confirm event wfh for 24.12.2030?", yes_no_menu()],
+                                     ["yes", "Applied: wfh for 24.12.2030", None]);
+
+        assert_eq!(events.len(), 2);
+        match events[0] {
+            Event::PartialDay(ref event) => {
+                let payload = serde_json::to_string(event).unwrap();
+                assert!(payload.contains("\"start\":{\"dateTime\":\"2031-07-01T10:00:00+02:00\",\"timeZone\":\"Europe/Berlin\"}"));
+            }
+            ref event => panic!("unexpected event {:?}", event),
+        }
+        match events[1] {
+            Event::WholeDay(ref event) => {
+                let payload = serde_json::to_string(event).unwrap();
+                assert!(payload.contains("\"end\":{\"date\":\"2030-12-24\",\"timeZone\":\"Europe/Berlin\"}"));
+            }
+            ref event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn test_daylight_saving_gap() {
+        // 02:00-03:00 is skipped in Berlin on 30.03.2031
+        let (events, _) =
+            define_menu_dialog_test!("Vasiliy",
+                                     Some("Pupkin"),
+                                     ["/settz Europe/Berlin", "Your time zone is Europe/Berlin", None],
+                                     ["/wfh 30.03.2031 from 2:30 to 4:00",
+                                      "Couldn't recognize \"30.03.2031 from 2:30 to 4:00\". When?",
+                                      when_menu()],
+                                     ["other",
+                                      "Which dates? Send a single date like \"24.12\" or a range like \"24.12-28.12\"",
+                                      None],
+                                     ["30.03.2031 from 3:00 to 4:00",
+                                      "Confirm event wfh on 30.03.2031 from 03:00 to 04:00?",
+                                      yes_no_menu()],
+                                     ["no", "Canceled!", None]);
+
+        assert_eq!(events.len(), 0);
+    }
+
+    #[test]
+    fn test_wfh_with_unrecognized_arguments() {
+        let (events, _) =
+            define_menu_dialog_test!("Vasiliy",
+                                     Some("Pupkin"),
+                                     ["/wfh someday", "Couldn't recognize \"someday\". When?", when_menu()],
+                                     ["tomorrow", "Confirm event wfh for tomorrow?", yes_no_menu()],
+                                     ["no", "Canceled!", None]);
+
+        assert_eq!(events.len(), 0);
+    }
+
+    #[test]
+    fn test_vacation() {
+        let (events, channel_messages) =
+            define_menu_dialog_test!("Vasiliy",
+                                     Some("Pupkin"),
+                                     ["/vacation 24.12.2030-28.12.2030",
+                                      "Confirm event vacation from 24.12.2030 to 28.12.2030?",
+                                      yes_no_menu()],
+                                     ["yes", "Applied: vacation from 24.12.2030 to 28.12.2030", None],
+                                     ["/sick", "When?", when_menu()],
+                                     ["today", "Confirm event sick day for today?", yes_no_menu()],
+                                     ["no", "Canceled!", None]);
+
+        assert_eq!(events.len(), 1);
+        match events[0] {
+            Event::WholeDay(ref event) => {
+                assert!(serde_json::to_string(event).unwrap().contains("\"summary\":\"Vacation: V.Pupkin\""))
+            }
+            ref other => panic!("Unexpected event {:?}", other),
+        }
+        assert_eq!(channel_messages,
+                   vec!["vacation from 24.12.2030 to 28.12.2030".to_string()]);
+    }
+
+    #[test]
+    fn test_my_events() {
+        let my_events_menu = Some(vec![vec!["delete 1".to_string(), "move 1".to_string()]]);
+        let (events, channel_messages) =
+            define_menu_dialog_test!("Vasiliy",
+                                     Some("Pupkin"),
+                                     ["/myevents", "You have no upcoming events", None],
+                                     ["/wfh 24.12.2030", "Confirm event wfh for 24.12.2030?", yes_no_menu()],
+                                     ["yes", "Applied: wfh for 24.12.2030", None],
+                                     ["/myevents",
+                                      "Your upcoming events:\n1. \"WFH: V.Pupkin\" for 24.12.2030",
+                                      my_events_menu.clone()],
+                                     ["move 1",
+                                      "Which dates? Send a single date like \"24.12\" or a range like \"24.12-28.12\"",
+                                      None],
+                                     ["25.12.2030 after 15:00", "Moved!", None],
+                                     ["/myevents",
+                                      "Your upcoming events:\n1. \"WFH: V.Pupkin\" on 25.12.2030 from 15:00 to 20:00",
+                                      my_events_menu.clone()],
+                                     ["delete 1",
+                                      "Delete \"WFH: V.Pupkin\" on 25.12.2030 from 15:00 to 20:00?",
+                                      yes_no_menu()],
+                                     ["yes", "Deleted!", None],
+                                     ["/myevents", "You have no upcoming events", None]);
+
+        assert_eq!(events.len(), 3);
+        match events[1] {
+            Event::Updated(ref id, CalendarEvent::PartialDay(_)) => assert_eq!(id, "event0"),
+            ref other => panic!("Unexpected event {:?}", other),
+        }
+        match events[2] {
+            Event::Deleted(ref id) => assert_eq!(id, "event0"),
+            ref other => panic!("Unexpected event {:?}", other),
+        }
+        assert_eq!(channel_messages,
+                   vec!["wfh for 24.12.2030".to_string(),
+                        "wfh for 24.12.2030 is rescheduled, now on 25.12.2030 from 15:00 to 20:00".to_string(),
+                        "wfh on 25.12.2030 from 15:00 to 20:00 is canceled".to_string()]);
+    }
+
+    fn text_update(chat_id: ChatID, first_name: &str, last_name: Option<&str>, text: &str) -> IncomingUpdate {
+        IncomingUpdate::from_text(chat_id, SenderIdentity::new(first_name, last_name), text)
+    }
+
+    fn send_message(message_processor: &mut UserCollection,
+                    message_sender: &mut MockMessageSender,
+                    chat_id: i64,
+                    first_name: &str,
+                    last_name: &str,
+                    text: &str) {
+        message_processor.process_update(message_sender,
+                                         text_update(ChatID::new(chat_id), first_name, Some(last_name), text))
+            .unwrap();
+    }
+
+    fn post_team_absences(message_processor: &mut UserCollection, message_sender: &mut MockMessageSender) {
+        let requests = [(1, "John", "Doe", "/wfh 24.12.2030"),
+                        (2, "Kim", "Lee", "/vacation 23.12.2030-26.12.2030"),
+                        (3, "Anna", "Smith", "/wfh 24.12.2030"),
+                        (4, "Bob", "Brown", "/wfh 24.12.2030 after 14:00"),
+                        (5, "Carl", "White", "/wfh 25.12.2030")];
+        send_message(message_processor, message_sender, 4, "Bob", "Brown", "/setteam support");
+        for &(chat_id, first_name, last_name, text) in requests.iter() {
+            send_message(message_processor, message_sender, chat_id, first_name, last_name, text);
+            send_message(message_processor, message_sender, chat_id, first_name, last_name, "yes");
+        }
+    }
+
+    #[test]
+    fn test_who() {
+        init_dialog_types();
+        init_configuration(&configuration());
+
+        let mut message_sender = MockMessageSender::new();
+        let mut events_sender = MockEventsSender::new();
+        let mut data_saver = MockDataSaver::new();
+        {
+            let mut message_processor = super::UserCollection::new(&mut events_sender,
+                                                                   &mut data_saver);
+            post_team_absences(&mut message_processor, &mut message_sender);
+            message_sender.messages.clear();
+
+            for text in &["/who 24.12.2030", "/who 24.12.2030-26.12.2030", "/who 01.01.2031", "/who someday"] {
+                send_message(&mut message_processor, &mut message_sender, 1, "John", "Doe", text);
+            }
+            send_message(&mut message_processor, &mut message_sender, 4, "Bob", "Brown", "/who 24.12.2030");
+        }
+
+        let replies: Vec<&str> = message_sender.messages.iter().map(|message| message.text.as_str()).collect();
+        assert_eq!(replies,
+                   vec!["Away on 24.12.2030:\nWFH – A.Smith, J.Doe\nVacation – K.Lee",
+                        "Away from 24.12.2030 to 26.12.2030:\n\
+                         WFH – A.Smith (24.12), C.White (25.12), J.Doe (24.12)\n\
+                         Vacation – K.Lee (23.12-26.12)",
+                        "Nobody is away on 01.01.2031",
+                        "Couldn't recognize dates in \"someday\"",
+                        "Away on 24.12.2030:\nWFH – B.Brown (14:00-22:00)"]);
+    }
+
+    #[test]
+    fn test_whereis() {
+        init_dialog_types();
+        init_configuration(&configuration());
+
+        let mut message_sender = MockMessageSender::new();
+        let mut events_sender = MockEventsSender::new();
+        let mut data_saver = MockDataSaver::new();
+        {
+            let mut message_processor = super::UserCollection::new(&mut events_sender,
+                                                                   &mut data_saver);
+            post_team_absences(&mut message_processor, &mut message_sender);
+            message_sender.messages.clear();
+
+            for text in &["/whereis K.Lee 20.12.2030-31.12.2030",
+                          "/whereis bob 24.12.2030",
+                          "/whereis anna 01.01.2031",
+                          "/whereis",
+                          "/whereis nobody"] {
+                send_message(&mut message_processor, &mut message_sender, 1, "John", "Doe", text);
+            }
+        }
+
+        let replies: Vec<&str> = message_sender.messages.iter().map(|message| message.text.as_str()).collect();
+        assert_eq!(replies,
+                   vec!["K.Lee from 20.12.2030 to 31.12.2030:\nVacation from 23.12.2030 to 26.12.2030",
+                        "B.Brown on 24.12.2030:\nWFH on 24.12.2030 from 14:00 to 22:00",
+                        "A.Smith is not away on 01.01.2031",
+                        "Please specify a name, e.g. \"/whereis J.Doe tomorrow\"",
+                        "Couldn't find \"nobody\""]);
+    }
+
+    #[test]
+    fn test_reminders() {
+        init_dialog_types();
+        init_configuration(&configuration());
+
+        let mut message_sender = MockMessageSender::new();
+        let mut events_sender = MockEventsSender::new();
+        let mut data_saver = MockDataSaver::new();
+        {
+            let mut message_processor = super::UserCollection::new(&mut events_sender,
+                                                                   &mut data_saver);
+            // Monday
+            for &(chat_id, first_name, last_name) in &[(1, "John", "Doe"), (2, "Kim", "Lee")] {
+                send_message(&mut message_processor, &mut message_sender, chat_id, first_name, last_name, "/wfh 23.12.2030");
+                send_message(&mut message_processor, &mut message_sender, chat_id, first_name, last_name, "yes");
+            }
+            send_message(&mut message_processor, &mut message_sender, 2, "Kim", "Lee", "/reminders off");
+            send_message(&mut message_processor, &mut message_sender, 3, "Anna", "Smith", "/whoami");
+            message_sender.messages.clear();
+
+            let date = NaiveDate::from_ymd(2030, 12, 24);
+            for hour in &[9, 10, 11] {
+                let now = chrono::Local.from_local_datetime(&date.and_hms(*hour, 0, 0)).unwrap();
+                message_processor.process_reminders(&mut message_sender, now.with_timezone(&Utc));
+            }
+            send_message(&mut message_processor, &mut message_sender, 1, "John", "Doe", "today");
+        }
+
+        assert_eq!(message_sender.messages,
+                   vec![Message::new(ChatID::new(1),
+                                     "Haven't heard from you today. Any wfh? Send /reminders off to stop these reminders",
+                                     when_menu()),
+                        Message::new(ChatID::new(1), "Confirm event wfh for today?", yes_no_menu())]);
+    }
+
+    #[test]
+    fn test_unknown_dialog() {
+        init_dialog_types();
+        init_configuration(&configuration());
+
+        let mut dialogs_processor = DialogsProcessor::new();
+        dialogs_processor.active_dialog = Some(Box::new(absence::make_when_dialog().unwrap().0));
+        let saved = serde_json::to_string(&dialogs_processor).unwrap();
+        let loaded: DialogsProcessor = serde_json::from_str(&saved).unwrap();
+        assert!(loaded.active_dialog.is_some());
+
+        // e.g. saved by another build
+        let loaded: DialogsProcessor = serde_json::from_str(&saved.replace("wfh-dialog", "renamed-dialog")).unwrap();
+        assert!(loaded.active_dialog.is_none());
+        let loaded: DialogsProcessor = serde_json::from_str("{}").unwrap();
+        assert!(loaded.active_dialog.is_none());
+    }
+
+    #[test]
+    fn test_reminder_expiry() {
+        init_dialog_types();
+        init_configuration(&configuration());
+
+        let (dialog, _) = absence::make_when_dialog().unwrap();
+        let mut dialogs_processor = DialogsProcessor::new();
+        let hour = 60 * 60;
+        let menu_id = dialogs_processor.start(Box::new(dialog), 0, 12 * hour);
+        // longer than the idle time of dialogs started by users
+        assert!(dialogs_processor.is_menu_active(menu_id, 12 * hour));
+        assert!(!dialogs_processor.is_menu_active(menu_id, 12 * hour + 1));
+
+        let mut message_sender = MockMessageSender::new();
+        let mut events_sender = MockEventsSender::new();
+        let mut outbox = Outbox::new();
+        let mut user_info = UserInfo::new(chat_id(), "Vasiliy".to_string(), "Pupkin".to_string());
+        for message in &["tomorrow", "/wfh"] {
+            dialogs_processor.process(message,
+                                      None,
+                                      &mut user_info,
+                                      &mut message_sender,
+                                      &mut events_sender,
+                                      &mut outbox,
+                                      11 * hour);
+        }
+        // a dialog started by the user has the usual idle time
+        assert!(!dialogs_processor.is_idle(12 * hour));
+        assert!(dialogs_processor.is_idle(12 * hour + 1));
+    }
+
+    #[test]
+    fn test_daily_digest() {
+        init_dialog_types();
+        init_configuration(&configuration());
+
+        let mut message_sender = MockMessageSender::new();
+        let mut events_sender = MockEventsSender::new();
+        let mut data_saver = MockDataSaver::new();
+        {
+            let mut message_processor = super::UserCollection::new(&mut events_sender,
+                                                                   &mut data_saver);
+            let requests = [(1, "John", "Doe", "/wfh 24.12.2030"),
+                            (2, "Kim", "Lee", "/vacation 23.12.2030-26.12.2030"),
+                            (3, "Anna", "Smith", "/wfh 24.12.2030"),
+                            (4, "Bob", "Brown", "/setteam support"),
+                            (4, "Bob", "Brown", "/wfh 24.12.2030 after 14:00"),
+                            (5, "Carl", "White", "/wfh 25.12.2030")];
+            for &(chat_id, first_name, last_name, text) in requests.iter() {
+                for text in &[text, "yes"] {
+                    message_processor.process_update(&mut message_sender,
+                                                     text_update(ChatID::new(chat_id), first_name, Some(last_name), text))
+                        .unwrap();
+                }
+            }
+            message_sender.channel_messages.clear();
+            message_sender.channels.clear();
+
+            let date = NaiveDate::from_ymd(2030, 12, 24);
+            for hour in &[8, 9, 9, 10] {
+                message_processor.process_digest(&mut message_sender, date.and_hms(*hour, 0, 0));
+            }
+        }
+
+        assert_eq!(message_sender.channels,
+                   vec!["-1001234567890".to_string(), "@BigBrotherEvents".to_string()]);
+        assert_eq!(message_sender.channel_messages,
+                   vec!["Today: WFH – B.Brown (14:00-22:00)".to_string(),
+                        "Today: WFH – A.Smith, J.Doe; Vacation – K.Lee".to_string()]);
+    }
+
+    #[test]
+    fn test_recurring_wfh() {
+        init_dialog_types();
+        init_configuration(&configuration());
+
+        let mut message_sender = MockMessageSender::new();
+        let mut events_sender = MockEventsSender::new();
+        let mut data_saver = MockDataSaver::new();
+        {
+            let mut message_processor = super::UserCollection::new(&mut events_sender,
+                                                                   &mut data_saver);
+            // 03.01.2031 is friday
+            for text in &["/wfh every friday", "yes", "/who 03.01.2031", "/who 30.12.2030-31.12.2030",
+                          "/myevents", "stop 1", "yes"] {
+                send_message(&mut message_processor, &mut message_sender, 42, "Vasiliy", "Pupkin", text);
+            }
+        }
+
+        let replies: Vec<&str> = message_sender.messages.iter().map(|message| message.text.as_str()).collect();
+        assert_eq!(replies[0], "Confirm event wfh every friday?");
+        assert!(replies[1].starts_with("Applied: wfh every friday from "));
+        assert_eq!(replies[2..4],
+                   ["Away on 03.01.2031:\nWFH – V.Pupkin",
+                    "Nobody is away from 30.12.2030 to 31.12.2030"]);
+        assert!(replies[4].starts_with("Your upcoming events:\n1. \"WFH: V.Pupkin\" every friday from "));
+        assert_eq!(message_sender.messages[4].menu,
+                   Some(vec![vec!["delete 1".to_string(), "stop 1".to_string()]]));
+        assert_eq!(replies[6], "Stopped!");
+        assert!(message_sender.channel_messages[1].ends_with(" is stopped"));
+
+        // whether the series is updated or deleted depends on today, see the tests of my_events
+        let events = &events_sender.events;
+        assert_eq!(events.len(), 2);
+        match events[0] {
+            Event::WholeDay(ref event) => {
+                assert!(serde_json::to_string(event)
+                            .unwrap()
+                            .contains("\"recurrence\":[\"RRULE:FREQ=WEEKLY;BYDAY=FR\"]"));
+                assert_eq!(event.get_start_date().weekday(), Weekday::Fri);
+            }
+            ref other => panic!("Unexpected event {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_calendar_failure() {
+        init_dialog_types();
+        init_configuration(&configuration());
+
+        let mut message_sender = MockMessageSender::new();
+        let mut events_sender = MockEventsSender::failing(vec![EventsError::Rejected(400, "test".to_string())]);
+        let mut data_saver = MockDataSaver::new();
+        {
+            let mut message_processor = super::UserCollection::new(&mut events_sender,
+                                                                   &mut data_saver);
+            for text in &["/wfh 24.12.2030", "yes", "/myevents"] {
+                message_processor.process_update(&mut message_sender, text_update(chat_id(), "Vasiliy", Some("Pupkin"), text)).unwrap();
+            }
+            message_processor.process_outbox(&mut message_sender, Utc::now().timestamp() + 24 * 60 * 60);
+        }
+
+        assert_eq!(message_sender.messages,
+                   vec![Message::new(chat_id(), "Confirm event wfh for 24.12.2030?", yes_no_menu()),
+                        Message::new(chat_id(), "Failed: calendar rejected the event (400 test)", None),
+                        Message::new(chat_id(), "You have no upcoming events", None)]);
+        assert!(message_sender.channel_messages.is_empty());
+    }
+
+    #[test]
+    fn test_delete_missing_event() {
+        init_dialog_types();
+        init_configuration(&configuration());
+
+        let mut message_sender = MockMessageSender::new();
+        let mut events_sender = MockEventsSender::new();
+        events_sender.delete_failures = vec![EventsError::Rejected(410, "Resource has been deleted".to_string()),
+                                             EventsError::Rejected(403, "test".to_string())];
+        let mut data_saver = MockDataSaver::new();
+        {
+            let mut message_processor = super::UserCollection::new(&mut events_sender,
+                                                                   &mut data_saver);
+            for text in &["/wfh 24.12.2030", "yes", "/wfh 25.12.2030", "yes",
+                          "/myevents", "delete 1", "yes", "/myevents", "delete 1", "yes", "/myevents"] {
+                message_processor.process_update(&mut message_sender, text_update(chat_id(), "Vasiliy", Some("Pupkin"), text)).unwrap();
+            }
+        }
+
+        let replies: Vec<&str> = message_sender.messages[4..].iter().map(|message| message.text.as_str()).collect();
+        assert_eq!(replies,
+                   vec!["Your upcoming events:\n1. \"WFH: V.Pupkin\" for 24.12.2030\n2. \"WFH: V.Pupkin\" for 25.12.2030",
+                        "Delete \"WFH: V.Pupkin\" for 24.12.2030?",
+                        "Deleted!",
+                        "Your upcoming events:\n1. \"WFH: V.Pupkin\" for 25.12.2030",
+                        "Delete \"WFH: V.Pupkin\" for 25.12.2030?",
+                        "Failed: calendar rejected the event (403 test)",
+                        "Your upcoming events:\n1. \"WFH: V.Pupkin\" for 25.12.2030"]);
+        assert_eq!(events_sender.events.len(), 2);
+    }
+
+    #[test]
+    fn test_stored_event_dates() {
+        let stored = r#"{"WholeDay":{"summary":"WFH: V.Pupkin","start":{"date":"2030-12-24"},"end":{"date":"2030-12-25"}}}"#;
+        let event: CalendarEvent = serde_json::from_str(stored).unwrap();
+        assert_eq!(event.get_end_date(), NaiveDate::from_ymd(2030, 12, 25));
+        assert_eq!(serde_json::to_string(&event).unwrap(), stored);
+
+        let stored = r#"{"PartialDay":{"summary":"WFH: V.Pupkin","start":{"dateTime":"2030-12-24T14:00:00+03:00"},"end":{"dateTime":"2030-12-24T18:00:00+03:00"}}}"#;
+        let event: CalendarEvent = serde_json::from_str(stored).unwrap();
+        assert_eq!(event.get_start_date(), NaiveDate::from_ymd(2030, 12, 24));
+        assert_eq!(serde_json::to_string(&event).unwrap(), stored);
+
+        // a corrupt data file fails to load instead of crashing the bot later
+        assert!(serde_json::from_str::<CalendarEvent>(&stored.replace("2030-12-24T18", "2030-12-24 x")).is_err());
+        assert!(serde_json::from_str::<CalendarEvent>(r#"{"WholeDay":{"summary":"","start":{"date":"24.12.2030"},"end":{"date":"2030-12-25"}}}"#).is_err());
+    }
+
+    #[test]
+    fn test_calendar_retry() {
+        init_dialog_types();
+        init_configuration(&configuration());
+
+        let mut message_sender = MockMessageSender::new();
+        let mut events_sender = MockEventsSender::failing(vec![EventsError::Connection("test".to_string()),
+                                                               EventsError::Rejected(503, "test".to_string())]);
+        let mut data_saver = MockDataSaver::new();
+        {
+            let mut message_processor = super::UserCollection::new(&mut events_sender,
+                                                                   &mut data_saver);
+            for text in &["/wfh 24.12.2030", "yes"] {
+                message_processor.process_update(&mut message_sender, text_update(chat_id(), "Vasiliy", Some("Pupkin"), text)).unwrap();
+            }
+
+            let now = Utc::now().timestamp();
+            // not due yet
+            message_processor.process_outbox(&mut message_sender, now);
+            // fails with 503 and is rescheduled
+            message_processor.process_outbox(&mut message_sender, now + 60 * 60);
+            message_processor.process_outbox(&mut message_sender, now + 3 * 60 * 60);
+            // already posted
+            message_processor.process_outbox(&mut message_sender, now + 24 * 60 * 60);
+
+            message_processor.process_update(&mut message_sender, text_update(chat_id(), "Vasiliy", Some("Pupkin"), "/myevents")).unwrap();
+        }
+
+        assert_eq!(message_sender.messages[..3],
+                   [Message::new(chat_id(), "Confirm event wfh for 24.12.2030?", yes_no_menu()),
+                    Message::new(chat_id(),
+                                 "Postponed: calendar is unreachable (test). The event will be posted as soon as possible",
+                                 None),
+                    Message::new(chat_id(), "Posted postponed wfh for 24.12.2030", None)]);
+        assert_eq!(message_sender.messages[3].text,
+                   "Your upcoming events:\n1. \"WFH: V.Pupkin\" for 24.12.2030");
+        assert_eq!(message_sender.messages.len(), 4);
+        assert_eq!(message_sender.channel_messages, vec!["wfh for 24.12.2030".to_string()]);
+
+        assert_eq!(events_sender.events.len(), 1);
+        match events_sender.events[0] {
+            Event::WholeDay(ref event) => assert!(event.get_id().unwrap().starts_with("bb")),
+            ref other => panic!("Unexpected event {:?}", other),
+        }
+    }
 }
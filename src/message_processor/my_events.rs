@@ -0,0 +1,288 @@
+use message_processor::dialog_processing::{YES_NO_MENU, DialogAction, ReplyMessage,
+                                           DialogInitializationResult, DynamicSerializable,
+                                           StaticNameGetter, Dialog, Event, ChannelMessage,
+                                           CommandDescription};
+use message_processor::absence::{DATES_FORMAT_HINT, describe_event, make_moved_event};
+use message_processor::date_parsing::parse_when;
+use message_processor::teams::get_working_hours;
+use configuration::WorkingHours;
+use basic_structures::{CalendarEvent, Menu, UserTimeZone};
+use user_data::{UserInfo, UserEvent};
+
+use chrono::NaiveDate;
+use serde_json;
+
+const MY_EVENTS_COMMAND : &str = "/myevents";
+const DELETE : &str = "delete";
+const MOVE : &str = "move";
+const STOP : &str = "stop";
+
+fn is_recurring(event: &UserEvent) -> bool {
+    match event.event {
+        CalendarEvent::WholeDay(ref event) => event.is_recurring(),
+        CalendarEvent::PartialDay(_) => false,
+    }
+}
+
+fn describe_user_event(event: &UserEvent) -> String {
+    format!("\"{}\" {}", event.event.get_summary(), describe_event(&event.event))
+}
+
+/// Past events are kept for reminders and queries, only upcoming ones are shown.
+fn get_upcoming_events<'a>(user_info: &'a UserInfo, today: &NaiveDate) -> Vec<&'a UserEvent> {
+    user_info.events
+        .iter()
+        .filter(|event| event.event.get_last_date().map_or(true, |last| last >= *today))
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct ChooseActionState {
+    /// Ids of the shown events by their numbers
+    #[serde(default)]
+    event_ids: Vec<String>,
+}
+
+impl ChooseActionState {
+    fn try_process(&mut self, text: &str, user_info: &mut UserInfo) -> (MyEventsState, DialogAction) {
+        let mut words = text.split_whitespace();
+        let action = words.next();
+        let event = words.next()
+            .and_then(|number| number.parse::<usize>().ok())
+            .and_then(|number| if number > 0 { self.event_ids.get(number - 1) } else { None })
+            .and_then(|id| user_info.events.iter().find(|event| event.id == *id))
+            .cloned();
+
+        match (action, event) {
+            (Some(DELETE), Some(event)) => {
+                let question = format!("Delete {}?", describe_user_event(&event));
+                (MyEventsState::ConfirmDelete(event),
+                 DialogAction::ProcessAndContinue(Some(ReplyMessage::new(question, Some(YES_NO_MENU.clone()))),
+                                                  None))
+            }
+            (Some(MOVE), Some(event)) if !is_recurring(&event) => {
+                (MyEventsState::ChooseNewDates(event),
+                 DialogAction::ProcessAndContinue(Some(ReplyMessage::new(DATES_FORMAT_HINT, None)), None))
+            }
+            (Some(STOP), Some(event)) if is_recurring(&event) => {
+                let question = format!("Stop {}?", describe_user_event(&event));
+                (MyEventsState::ConfirmStop(event),
+                 DialogAction::ProcessAndContinue(Some(ReplyMessage::new(question, Some(YES_NO_MENU.clone()))),
+                                                  None))
+            }
+            _ => (MyEventsState::ChooseAction(ChooseActionState::default()), DialogAction::Stop),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+enum MyEventsState {
+    ChooseAction(ChooseActionState),
+    ConfirmDelete(UserEvent),
+    ChooseNewDates(UserEvent),
+    ConfirmStop(UserEvent),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MyEventsDialog {
+    state: MyEventsState,
+}
+
+fn process_delete(text: &str, event: &UserEvent) -> DialogAction {
+    if text == "yes" {
+        let channel_message = format!("{} {} is canceled", event.kind, describe_event(&event.event));
+        DialogAction::ProcessAndStop(Some(ReplyMessage::new("Deleted!", None)),
+                                     Some(Event::Delete(event.clone())),
+                                     Some(ChannelMessage::new(channel_message)))
+    } else {
+        DialogAction::ProcessAndStop(Some(ReplyMessage::new("Canceled!", None)), None, None)
+    }
+}
+
+/// Keeps past occurrences, the recurrence which hasn't started yet is deleted.
+fn process_stop(text: &str, event: &UserEvent, today: &NaiveDate) -> DialogAction {
+    if text != "yes" {
+        return DialogAction::ProcessAndStop(Some(ReplyMessage::new("Canceled!", None)), None, None);
+    }
+
+    let channel_message = format!("{} {} is stopped", event.kind, describe_event(&event.event));
+    let calendar_event = match event.event {
+        CalendarEvent::WholeDay(ref whole_day) if whole_day.get_start_date() <= *today => {
+            let mut whole_day = whole_day.clone();
+            whole_day.stop_recurrence(today);
+            Event::Update(event.clone(), CalendarEvent::WholeDay(whole_day))
+        }
+        _ => Event::Delete(event.clone()),
+    };
+    DialogAction::ProcessAndStop(Some(ReplyMessage::new("Stopped!", None)),
+                                 Some(calendar_event),
+                                 Some(ChannelMessage::new(channel_message)))
+}
+
+fn process_move(text: &str, event: &UserEvent, working_hours: &WorkingHours, zone: &UserTimeZone) -> DialogAction {
+    let moved_event = parse_when(text, &zone.today().naive_local())
+        .and_then(|when| make_moved_event(&event.event, &when, working_hours, zone));
+    match moved_event {
+        Some(moved_event) => {
+            let channel_message = format!("{} {} is rescheduled, now {}",
+                                          event.kind,
+                                          describe_event(&event.event),
+                                          describe_event(&moved_event));
+            DialogAction::ProcessAndStop(Some(ReplyMessage::new("Moved!", None)),
+                                         Some(Event::Update(event.clone(), moved_event)),
+                                         Some(ChannelMessage::new(channel_message)))
+        }
+        None => {
+            DialogAction::ProcessAndStop(Some(ReplyMessage::new(format!("Couldn't recognize dates in \"{}\". Canceled!", text),
+                                                                None)),
+                                         None,
+                                         None)
+        }
+    }
+}
+
+impl Dialog for MyEventsDialog {
+    fn try_process(&mut self, text: &str, user_info: &mut UserInfo) -> DialogAction {
+        let (state, result) = match self.state {
+            MyEventsState::ChooseAction(ref mut state) => state.try_process(text, user_info),
+            MyEventsState::ConfirmDelete(ref event) => {
+                (MyEventsState::ChooseAction(ChooseActionState::default()), process_delete(text, event))
+            }
+            MyEventsState::ConfirmStop(ref event) => {
+                (MyEventsState::ChooseAction(ChooseActionState::default()),
+                 process_stop(text, event, &user_info.get_time_zone().today().naive_local()))
+            }
+            MyEventsState::ChooseNewDates(ref event) => {
+                (MyEventsState::ChooseAction(ChooseActionState::default()),
+                 process_move(text, event, &get_working_hours(user_info), &user_info.get_time_zone()))
+            }
+        };
+
+        self.state = state;
+
+        result
+    }
+
+    fn make(initial_message: &str, user_info: &mut UserInfo) -> DialogInitializationResult {
+        if initial_message.split_whitespace().next() != Some(MY_EVENTS_COMMAND) {
+            return DialogInitializationResult::NotProcessed;
+        }
+
+        let today = user_info.get_time_zone().today().naive_local();
+        let events = get_upcoming_events(user_info, &today);
+        if events.is_empty() {
+            return DialogInitializationResult::Finished(Some(ReplyMessage::new("You have no upcoming events",
+                                                                               None)),
+                                                        None);
+        }
+
+        let mut text = "Your upcoming events:".to_string();
+        let mut menu = Menu::new();
+        for (index, event) in events.iter().enumerate() {
+            let number = index + 1;
+            text.push_str(&format!("\n{}. {}", number, describe_user_event(event)));
+            let action = if is_recurring(event) { STOP } else { MOVE };
+            menu.push(vec![format!("{} {}", DELETE, number), format!("{} {}", action, number)]);
+        }
+
+        let state = ChooseActionState { event_ids: events.iter().map(|event| event.id.clone()).collect() };
+        let dialog = Self { state: MyEventsState::ChooseAction(state) };
+        DialogInitializationResult::StartedProcessing(Some(ReplyMessage::new(text, Some(menu))),
+                                                      None,
+                                                      Box::new(dialog))
+    }
+
+    fn get_commands() -> Vec<CommandDescription> {
+        vec![CommandDescription::new(MY_EVENTS_COMMAND, "Show your upcoming events to delete or move them")
+                 .with_example(MY_EVENTS_COMMAND)]
+    }
+}
+
+impl DynamicSerializable for MyEventsDialog {
+    fn to_string(&self) -> String {
+        serde_json::to_string(self).unwrap() // FIXME
+    }
+
+    fn from_string(string: &str) -> Self {
+        serde_json::from_str::<Self>(string).unwrap() // FIXME
+    }
+}
+
+impl StaticNameGetter for MyEventsDialog {
+    fn get_name() -> &'static str {
+        return "myevents-dialog";
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use basic_structures::WholeDayEvent;
+    use user_data::ChatID;
+
+    use chrono::{FixedOffset, TimeZone, Weekday};
+
+    // friday
+    fn series() -> UserEvent {
+        let start = FixedOffset::east(0).ymd(2030, 12, 20);
+        let event = WholeDayEvent::new_weekly("WFH: V.Pupkin".to_string(), &start, &[Weekday::Fri]);
+        UserEvent::new("event0".to_string(), "wfh".to_string(), None, CalendarEvent::WholeDay(event))
+    }
+
+    fn get_event(action: DialogAction) -> Event {
+        match action {
+            DialogAction::ProcessAndStop(_, Some(event), _) => event,
+            _ => panic!("No event"),
+        }
+    }
+
+    #[test]
+    fn test_stop_started_series() {
+        let today = NaiveDate::from_ymd(2031, 1, 8);
+        match get_event(process_stop("yes", &series(), &today)) {
+            // past occurrences are kept
+            Event::Update(ref event, CalendarEvent::WholeDay(ref stopped)) => {
+                assert_eq!(event.id, "event0");
+                assert_eq!(stopped.get_start_date(), NaiveDate::from_ymd(2030, 12, 20));
+                assert_eq!(stopped.get_recurrence_end(), Some(today));
+            }
+            _ => panic!("The series isn't updated"),
+        }
+    }
+
+    #[test]
+    fn test_stop_future_series() {
+        match get_event(process_stop("yes", &series(), &NaiveDate::from_ymd(2030, 12, 19))) {
+            Event::Delete(ref event) => assert_eq!(event.id, "event0"),
+            _ => panic!("The series isn't deleted"),
+        }
+    }
+
+    #[test]
+    fn test_past_events_kept() {
+        let zone = FixedOffset::east(0);
+        let event = |id: &str, year: i32| {
+            let event = WholeDayEvent::new(format!("WFH {}", year), &zone.ymd(year, 12, 24), &zone.ymd(year, 12, 24));
+            UserEvent::new(id.to_string(), "wfh".to_string(), None, CalendarEvent::WholeDay(event))
+        };
+        let mut user_info = UserInfo::new(ChatID::new(42), "Vasiliy".to_string(), "Pupkin".to_string());
+        user_info.events = vec![event("past", 2020), event("upcoming", 2030)];
+
+        let mut dialog = match MyEventsDialog::make(MY_EVENTS_COMMAND, &mut user_info) {
+            DialogInitializationResult::StartedProcessing(Some(ref reply), _, dialog) => {
+                assert_eq!(reply.text, "Your upcoming events:\n1. \"WFH 2030\" for 24.12.2030");
+                dialog
+            }
+            _ => panic!("No events are shown"),
+        };
+        // the history is used by reminders and queries
+        assert_eq!(user_info.events.len(), 2);
+        match dialog.try_process("delete 1", &mut user_info) {
+            DialogAction::ProcessAndContinue(Some(ref reply), _) => {
+                assert_eq!(reply.text, "Delete \"WFH 2030\" for 24.12.2030?")
+            }
+            _ => panic!("The event isn't chosen"),
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use message_processor::absence::{get_absence_kinds, describe_event};
+use message_processor::date_parsing::{When, parse_when};
+use message_processor::dialog_processing::CommandDescription;
+use basic_structures::CalendarEvent;
+use user_data::{UserInfo, UserEvent};
+
+use chrono::NaiveDate;
+use time;
+
+use std::sync::Mutex;
+
+const WHO_COMMAND: &str = "/who";
+const WHERE_IS_COMMAND: &str = "/whereis";
+
+lazy_static! {
+    static ref QUERY_DAYS: Mutex<u32> = Mutex::new(7);
+}
+
+pub fn get_who_commands() -> Vec<CommandDescription> {
+    vec![CommandDescription::new(WHO_COMMAND, "Show who is away from your team")
+             .with_example(WHO_COMMAND)
+             .with_example(format!("{} tomorrow", WHO_COMMAND))
+             .with_example(format!("{} 24.12-28.12", WHO_COMMAND))]
+}
+
+pub fn get_where_is_commands() -> Vec<CommandDescription> {
+    vec![CommandDescription::new(WHERE_IS_COMMAND, "Show absences of a colleague")
+             .with_example(format!("{} J.Doe", WHERE_IS_COMMAND))
+             .with_example(format!("{} J.Doe tomorrow", WHERE_IS_COMMAND))]
+}
+
+/// Number of days starting from today covered by queries without dates.
+pub fn register_query_days(days: u32) {
+    *QUERY_DAYS.lock().unwrap() = days;
+}
+
+fn get_default_period(today: &NaiveDate) -> (NaiveDate, NaiveDate) {
+    let days = *QUERY_DAYS.lock().unwrap() as i64;
+    (*today, *today + time::Duration::days(days - 1))
+}
+
+fn overlaps(event: &UserEvent, start: &NaiveDate, end: &NaiveDate) -> bool {
+    event.event.occurs_between(start, end)
+}
+
+fn get_name(user_info: &UserInfo) -> &String {
+    user_info.get_calendar_name().unwrap_or(user_info.get_first_name())
+}
+
+/// Hours for a single day period, dates otherwise: "14:00-20:00", "24.12-26.12", "24.12 14:00-20:00",
+/// "every friday from 27.12.2030".
+fn describe_period(calendar_event: &CalendarEvent, is_single_day: bool) -> Option<String> {
+    match calendar_event {
+        &CalendarEvent::WholeDay(_) if is_single_day => None,
+        &CalendarEvent::WholeDay(ref event) if event.is_recurring() => Some(describe_event(calendar_event)),
+        &CalendarEvent::WholeDay(ref event) => {
+            let (start, end) = (event.get_start_date(), event.get_end_date());
+            if start == end {
+                Some(start.format("%d.%m").to_string())
+            } else {
+                Some(format!("{}-{}", start.format("%d.%m"), end.format("%d.%m")))
+            }
+        }
+        &CalendarEvent::PartialDay(ref event) => {
+            let hours = format!("{}-{}",
+                                event.get_start_time().format("%H:%M"),
+                                event.get_end_time().format("%H:%M"));
+            if is_single_day {
+                Some(hours)
+            } else {
+                Some(format!("{} {}", event.get_start_time().format("%d.%m"), hours))
+            }
+        }
+    }
+}
+
+/// Lines like "WFH – A.Smith, J.Doe (14:00-20:00)" for users away in the period, in order of absence kinds.
+pub fn describe_absences<'a, I>(users: I, start: &NaiveDate, end: &NaiveDate) -> Vec<String>
+    where I: Iterator<Item = &'a UserInfo> + Clone
+{
+    let is_single_day = start == end;
+    let mut result = Vec::new();
+    for kind in get_absence_kinds().iter() {
+        let mut names: Vec<String> = users.clone()
+            .flat_map(|user_info| {
+                user_info.events
+                    .iter()
+                    .filter(|event| event.kind == kind.name && overlaps(event, start, end))
+                    .map(move |event| match describe_period(&event.event, is_single_day) {
+                             Some(period) => format!("{} ({})", get_name(user_info), period),
+                             None => get_name(user_info).clone(),
+                         })
+            })
+            .collect();
+        if names.is_empty() {
+            continue;
+        }
+        names.sort();
+        names.dedup();
+        result.push(format!("{} – {}", kind.calendar_prefix, names.join(", ")));
+    }
+    result
+}
+
+fn describe_dates(start: &NaiveDate, end: &NaiveDate, today: &NaiveDate) -> String {
+    if start == end && start == today {
+        "today".to_string()
+    } else if start == end {
+        format!("on {}", start.format("%d.%m.%Y"))
+    } else {
+        format!("from {} to {}", start.format("%d.%m.%Y"), end.format("%d.%m.%Y"))
+    }
+}
+
+/// Whole days covered by the expression, `None` if it's not recognized.
+fn parse_period(text: &str, today: &NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+    match parse_when(text, today)? {
+        When::Days(start, end) => Some((start, end)),
+        When::After(date, _) |
+        When::Before(date, _) |
+        When::Between(date, _, _) |
+        When::Morning(date) |
+        When::Afternoon(date) => Some((date, date)),
+    }
+}
+
+/// "/who [dates]": members of the team away in the period, today by default.
+fn answer_who<'a, I>(arguments: &str, team: Option<&String>, users: I, today: &NaiveDate) -> String
+    where I: Iterator<Item = &'a UserInfo> + Clone
+{
+    let (start, end) = if arguments.is_empty() {
+        (*today, *today)
+    } else {
+        match parse_period(arguments, today) {
+            Some(period) => period,
+            None => return format!("Couldn't recognize dates in \"{}\"", arguments),
+        }
+    };
+
+    let team_users = users.filter(|user_info| user_info.team.as_ref() == team);
+    let lines = describe_absences(team_users, &start, &end);
+    let dates = describe_dates(&start, &end, today);
+    if lines.is_empty() {
+        format!("Nobody is away {}", dates)
+    } else {
+        format!("Away {}:\n{}", dates, lines.join("\n"))
+    }
+}
+
+fn is_matching_name(user_info: &UserInfo, name: &str) -> bool {
+    let name = name.to_lowercase();
+    user_info.get_calendar_name().map_or(false, |calendar_name| calendar_name.to_lowercase() == name) ||
+    user_info.get_first_name().to_lowercase() == name || user_info.get_last_name().to_lowercase() == name
+}
+
+/// "/whereis <name> [dates]": absences of the matching users, default period by default.
+fn answer_where_is<'a, I>(arguments: &str, users: I, today: &NaiveDate) -> String
+    where I: Iterator<Item = &'a UserInfo>
+{
+    let mut words = arguments.splitn(2, char::is_whitespace);
+    let name = words.next().unwrap_or("");
+    if name.is_empty() {
+        return format!("Please specify a name, e.g. \"{} J.Doe tomorrow\"", WHERE_IS_COMMAND);
+    }
+    let dates = words.next().unwrap_or("").trim();
+    let (start, end) = if dates.is_empty() {
+        get_default_period(today)
+    } else {
+        match parse_period(dates, today) {
+            Some(period) => period,
+            None => return format!("Couldn't recognize dates in \"{}\"", dates),
+        }
+    };
+
+    let kinds = get_absence_kinds();
+    let mut answers = Vec::new();
+    for user_info in users.filter(|user_info| is_matching_name(user_info, name)) {
+        let mut lines = Vec::new();
+        for kind in kinds.iter() {
+            for event in user_info.events.iter().filter(|event| event.kind == kind.name && overlaps(event, &start, &end)) {
+                lines.push(format!("{} {}", kind.calendar_prefix, describe_event(&event.event)));
+            }
+        }
+        answers.push(if lines.is_empty() {
+                         format!("{} is not away {}", get_name(user_info), describe_dates(&start, &end, today))
+                     } else {
+                         format!("{} {}:\n{}", get_name(user_info), describe_dates(&start, &end, today), lines.join("\n"))
+                     });
+    }
+
+    if answers.is_empty() {
+        format!("Couldn't find \"{}\"", name)
+    } else {
+        answers.join("\n\n")
+    }
+}
+
+fn get_arguments(message: &str) -> &str {
+    let command = message.split_whitespace().next().unwrap_or("");
+    message.trim()[command.len()..].trim()
+}
+
+/// Registered for "/who", see `QueryAnswer`.
+pub fn answer_who_query(message: &str, team: Option<&String>, users: &[&UserInfo], today: &NaiveDate) -> String {
+    answer_who(get_arguments(message), team, users.iter().cloned(), today)
+}
+
+/// Registered for "/whereis", see `QueryAnswer`.
+pub fn answer_where_is_query(message: &str, _: Option<&String>, users: &[&UserInfo], today: &NaiveDate) -> String {
+    answer_where_is(get_arguments(message), users.iter().cloned(), today)
+}
@@ -0,0 +1,112 @@
+use message_processor::dialog_processing::{ReplyMessage, DynamicSerializable, StaticNameGetter, Event,
+                                           CommandDescription};
+use message_processor::simple_dialogs::SimpleDialog;
+use user_data::UserInfo;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike, Weekday};
+use time;
+
+use serde_json;
+
+use std::sync::Mutex;
+
+const REMINDERS_COMMAND: &str = "/reminders";
+/// Users who had an absence during these days before today are reminded
+const ACTIVITY_DAYS: i64 = 7;
+
+lazy_static! {
+    static ref REMINDER_HOUR: Mutex<Option<u32>> = Mutex::new(None);
+}
+
+/// Reminders are disabled if the hour is not specified.
+pub fn register_reminder_hour(hour: Option<u32>) {
+    *REMINDER_HOUR.lock().unwrap() = hour;
+}
+
+/// Whether the user usually reports absences but hasn't said anything today.
+pub fn is_reminder_due(user_info: &UserInfo, now: &NaiveDateTime) -> bool {
+    let default_hour = match *REMINDER_HOUR.lock().unwrap() {
+        Some(hour) => hour,
+        None => return false,
+    };
+    let settings = &user_info.reminders;
+    let today = now.date();
+    if !settings.enabled || now.hour() < settings.hour.unwrap_or(default_hour) ||
+       settings.last_reminder == Some(today) || user_info.last_active == Some(today) {
+        return false;
+    }
+    if today.weekday() == Weekday::Sat || today.weekday() == Weekday::Sun {
+        return false;
+    }
+
+    let activity_start = today - time::Duration::days(ACTIVITY_DAYS);
+    let is_reported = |start: NaiveDate, end: NaiveDate| {
+        user_info.events.iter().any(|event| event.event.occurs_between(&start, &end))
+    };
+    is_reported(activity_start, today - time::Duration::days(1)) && !is_reported(today, today)
+}
+
+pub fn make_reminder_text(kind_name: &str) -> String {
+    format!("Haven't heard from you today. Any {}? Send {} off to stop these reminders",
+            kind_name,
+            REMINDERS_COMMAND)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RemindersDialog {}
+
+impl SimpleDialog for RemindersDialog {
+    fn process_message(message: &str,
+                       user_info: &mut UserInfo)
+                       -> Option<(Option<ReplyMessage>, Option<Event>)> {
+        if message.split_whitespace().next() != Some(REMINDERS_COMMAND) {
+            return None;
+        }
+
+        let argument = message.trim()[REMINDERS_COMMAND.len()..].trim();
+        let reply_message = match argument {
+            "off" => {
+                user_info.reminders.enabled = false;
+                "Reminders are off".to_string()
+            }
+            "on" => {
+                user_info.reminders.enabled = true;
+                "Reminders are on".to_string()
+            }
+            _ => {
+                match argument.parse::<u32>() {
+                    Ok(hour) if hour < 24 => {
+                        user_info.reminders.enabled = true;
+                        user_info.reminders.hour = Some(hour);
+                        format!("You will be reminded after {}:00", hour)
+                    }
+                    _ => format!("Please specify \"on\", \"off\" or an hour, e.g. \"{} 10\"", REMINDERS_COMMAND),
+                }
+            }
+        };
+
+        Some((Some(ReplyMessage::new(reply_message, None)), None))
+    }
+
+    fn get_commands() -> Vec<CommandDescription> {
+        vec![CommandDescription::new(REMINDERS_COMMAND, "Turn reminders on or off or change their hour")
+                 .with_example(format!("{} off", REMINDERS_COMMAND))
+                 .with_example(format!("{} 11", REMINDERS_COMMAND))]
+    }
+}
+
+impl DynamicSerializable for RemindersDialog {
+    fn to_string(&self) -> String {
+        serde_json::to_string(self).unwrap() // FIXME
+    }
+
+    fn from_string(string: &str) -> Self {
+        serde_json::from_str::<Self>(string).unwrap() // FIXME
+    }
+}
+
+impl StaticNameGetter for RemindersDialog {
+    fn get_name() -> &'static str {
+        return "reminders-dialog";
+    }
+}
@@ -1,7 +1,10 @@
 use message_processor::dialog_processing::{DialogAction, ReplyMessage, DialogInitializationResult,
-                                           DynamicSerializable, StaticNameGetter, Dialog, Event};
+                                           DynamicSerializable, StaticNameGetter, Dialog, Event,
+                                           CommandDescription};
+use message_processor::get_commands;
 use user_data::UserInfo;
 
+use chrono_tz::Tz;
 use serde_json;
 
 pub trait SimpleDialog {
@@ -9,6 +12,7 @@ pub trait SimpleDialog {
                        user_info: &mut UserInfo)
                        -> Option<(Option<ReplyMessage>, Option<Event>)>
         where Self: Sized;
+    fn get_commands() -> Vec<CommandDescription> where Self: Sized;
 }
 
 impl<T> Dialog for T
@@ -26,21 +30,64 @@ impl<T> Dialog for T
             None => DialogInitializationResult::NotProcessed,
         }
     }
+
+    fn get_commands() -> Vec<CommandDescription> {
+        <Self as SimpleDialog>::get_commands()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct HelpDialog {}
 
+const HELP_COMMAND: &str = "/help";
+
+fn describe_commands(commands: &[CommandDescription]) -> String {
+    let mut result = "Available commands:".to_string();
+    for description in commands {
+        result.push_str(&format!("\n{} – {}", description.command, description.description));
+    }
+    result.push_str(&format!("\nSend \"{} <command>\" for details", HELP_COMMAND));
+    result
+}
+
+fn describe_command(description: &CommandDescription) -> String {
+    let mut result = format!("{} – {}", description.command, description.description);
+    if !description.examples.is_empty() {
+        result.push_str("\nExamples:");
+        for example in &description.examples {
+            result.push_str(&format!("\n{}", example));
+        }
+    }
+    result
+}
+
 impl SimpleDialog for HelpDialog {
     fn process_message(message: &str,
                        _: &mut UserInfo)
                        -> Option<(Option<ReplyMessage>, Option<Event>)> {
-        if message.starts_with("/help") {
-            Some((Some(ReplyMessage::new("https://www.youtube.com/watch?v=yWP6Qki8mWc", None)),
-                  None))
-        } else {
-            None
+        if message.split_whitespace().next() != Some(HELP_COMMAND) {
+            return None;
         }
+
+        let commands = get_commands();
+        let argument = message[HELP_COMMAND.len()..].trim();
+        let reply_message = if argument.is_empty() {
+            describe_commands(&commands)
+        } else {
+            let command = if argument.starts_with('/') { argument.to_string() } else { format!("/{}", argument) };
+            match commands.iter().find(|description| description.command == command) {
+                Some(description) => describe_command(description),
+                None => format!("Unknown command \"{}\"\n\n{}", argument, describe_commands(&commands)),
+            }
+        };
+
+        Some((Some(ReplyMessage::new(reply_message, None)), None))
+    }
+
+    fn get_commands() -> Vec<CommandDescription> {
+        vec![CommandDescription::new(HELP_COMMAND, "Show the available commands or details of one of them")
+                 .with_example(HELP_COMMAND)
+                 .with_example(format!("{} /whoami", HELP_COMMAND))]
     }
 }
 
@@ -82,6 +129,10 @@ impl SimpleDialog for WhoAmIDialog {
             None
         }
     }
+
+    fn get_commands() -> Vec<CommandDescription> {
+        vec![CommandDescription::new("/whoami", "Show your name in the calendar").with_example("/whoami")]
+    }
 }
 
 impl DynamicSerializable for WhoAmIDialog {
@@ -130,6 +181,11 @@ impl SimpleDialog for SetMyNameDialog {
             None
         }
     }
+
+    fn get_commands() -> Vec<CommandDescription> {
+        vec![CommandDescription::new(SET_MY_NAME_PREFIX, "Change your name in the calendar")
+                 .with_example(format!("{} J.Doe", SET_MY_NAME_PREFIX))]
+    }
 }
 
 impl DynamicSerializable for SetMyNameDialog {
@@ -147,3 +203,50 @@ impl StaticNameGetter for SetMyNameDialog {
         return "setmyname-dialog";
     }
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SetTimeZoneDialog {}
+
+static SET_TIME_ZONE_PREFIX: &str = "/settz";
+
+impl SimpleDialog for SetTimeZoneDialog {
+    fn process_message(message: &str,
+                       user_info: &mut UserInfo)
+                       -> Option<(Option<ReplyMessage>, Option<Event>)> {
+        if message.split_whitespace().next() != Some(SET_TIME_ZONE_PREFIX) {
+            return None;
+        }
+
+        let name = message[SET_TIME_ZONE_PREFIX.len()..].trim();
+        let reply_message = match name.parse::<Tz>() {
+            Ok(zone) => {
+                user_info.time_zone = Some(zone.name().to_string());
+                format!("Your time zone is {}", zone.name())
+            }
+            Err(_) => "No valid time zone is specified. Please specify it in format \"/settz Europe/Berlin\"".to_string(),
+        };
+
+        Some((Some(ReplyMessage::new(reply_message, None)), None))
+    }
+
+    fn get_commands() -> Vec<CommandDescription> {
+        vec![CommandDescription::new(SET_TIME_ZONE_PREFIX, "Set your time zone")
+                 .with_example(format!("{} Europe/Berlin", SET_TIME_ZONE_PREFIX))]
+    }
+}
+
+impl DynamicSerializable for SetTimeZoneDialog {
+    fn to_string(&self) -> String {
+        serde_json::to_string(self).unwrap() // FIXME
+    }
+
+    fn from_string(string: &str) -> Self {
+        serde_json::from_str::<Self>(string).unwrap() // FIXME
+    }
+}
+
+impl StaticNameGetter for SetTimeZoneDialog {
+    fn get_name() -> &'static str {
+        return "settz-dialog";
+    }
+}
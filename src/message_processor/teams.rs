@@ -0,0 +1,109 @@
+use message_processor::dialog_processing::{ReplyMessage, DynamicSerializable, StaticNameGetter, Event,
+                                           CommandDescription};
+use message_processor::simple_dialogs::SimpleDialog;
+use configuration::{Team, WorkingHours};
+use user_data::UserInfo;
+
+use serde_json;
+
+use std::sync::Mutex;
+
+/// Settings used for users without a team and teams without own ones
+struct Teams {
+    working_hours: WorkingHours,
+    status_channel: String,
+    teams: Vec<Team>,
+}
+
+impl Teams {
+    fn find(&self, name: Option<&String>) -> Option<&Team> {
+        name.and_then(|name| self.teams.iter().find(|team| &team.name == name))
+    }
+}
+
+lazy_static! {
+    static ref TEAMS: Mutex<Teams> = Mutex::new(Teams {
+        working_hours: WorkingHours::default(),
+        status_channel: String::new(),
+        teams: Vec::new(),
+    });
+}
+
+pub fn register_teams(working_hours: WorkingHours, status_channel: String, teams: Vec<Team>) {
+    *TEAMS.lock().unwrap() = Teams {
+        working_hours,
+        status_channel,
+        teams,
+    };
+}
+
+pub fn get_working_hours(user_info: &UserInfo) -> WorkingHours {
+    let teams = TEAMS.lock().unwrap();
+    teams.find(user_info.team.as_ref())
+        .and_then(|team| team.working_hours)
+        .unwrap_or(teams.working_hours)
+}
+
+/// Channel for messages about events of the team's members.
+pub fn get_status_channel(team: Option<&String>) -> String {
+    let teams = TEAMS.lock().unwrap();
+    teams.find(team)
+        .and_then(|team| team.status_channel.clone())
+        .unwrap_or_else(|| teams.status_channel.clone())
+}
+
+fn get_team_names() -> Vec<String> {
+    TEAMS.lock().unwrap().teams.iter().map(|team| team.name.clone()).collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SetTeamDialog {}
+
+static SET_TEAM_PREFIX: &str = "/setteam";
+
+impl SimpleDialog for SetTeamDialog {
+    fn process_message(message: &str,
+                       user_info: &mut UserInfo)
+                       -> Option<(Option<ReplyMessage>, Option<Event>)> {
+        if message.split_whitespace().next() != Some(SET_TEAM_PREFIX) {
+            return None;
+        }
+
+        let team_names = get_team_names();
+        let name = message[SET_TEAM_PREFIX.len()..].trim();
+        let reply_message = if team_names.is_empty() {
+            "No teams are configured".to_string()
+        } else if team_names.iter().any(|team| team == name) {
+            user_info.team = Some(name.to_string());
+            format!("Your team is \"{}\"", name)
+        } else {
+            format!("Please specify one of the teams: {}", team_names.join(", "))
+        };
+
+        Some((Some(ReplyMessage::new(reply_message, None)), None))
+    }
+
+    fn get_commands() -> Vec<CommandDescription> {
+        let description = get_team_names()
+            .into_iter()
+            .fold(CommandDescription::new(SET_TEAM_PREFIX, "Choose your team"),
+                  |description, name| description.with_example(format!("{} {}", SET_TEAM_PREFIX, name)));
+        vec![description]
+    }
+}
+
+impl DynamicSerializable for SetTeamDialog {
+    fn to_string(&self) -> String {
+        serde_json::to_string(self).unwrap() // FIXME
+    }
+
+    fn from_string(string: &str) -> Self {
+        serde_json::from_str::<Self>(string).unwrap() // FIXME
+    }
+}
+
+impl StaticNameGetter for SetTeamDialog {
+    fn get_name() -> &'static str {
+        return "setteam-dialog";
+    }
+}
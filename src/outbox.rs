@@ -0,0 +1,119 @@
+use super::basic_structures::CalendarEvent;
+use super::user_data::ChatID;
+
+use std::cmp;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+const FIRST_RETRY_DELAY_SECONDS: i64 = 60;
+const MAX_RETRY_DELAY_SECONDS: i64 = 60 * 60;
+const MAX_ATTEMPTS: u32 = 10;
+
+/// Calendar event which couldn't be posted yet.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PendingEvent {
+    pub chat_id: ChatID,
+    /// Name of the absence kind, e.g. "wfh"
+    pub kind: String,
+    pub team: Option<String>,
+    /// Has client-side id set, so the calendar rejects duplicates
+    pub event: CalendarEvent,
+    pub channel_message: Option<String>,
+    attempts: u32,
+    next_attempt: i64,
+}
+
+impl PendingEvent {
+    pub fn new(chat_id: ChatID,
+               kind: String,
+               team: Option<String>,
+               event: CalendarEvent,
+               channel_message: Option<String>,
+               now: i64)
+               -> Self {
+        let mut result = Self {
+            chat_id,
+            kind,
+            team,
+            event,
+            channel_message,
+            attempts: 1,
+            next_attempt: now,
+        };
+        result.schedule_next_attempt(now);
+        result
+    }
+
+    fn get_key(&self) -> Option<&String> {
+        self.event.get_id()
+    }
+
+    fn schedule_next_attempt(&mut self, now: i64) {
+        let delay = FIRST_RETRY_DELAY_SECONDS
+            .checked_shl(self.attempts - 1)
+            .unwrap_or(MAX_RETRY_DELAY_SECONDS);
+        self.next_attempt = now + cmp::min(delay, MAX_RETRY_DELAY_SECONDS);
+    }
+}
+
+/// Id accepted by Google Calendar: 5-1024 characters of base32hex alphabet (a-v, 0-9).
+pub fn make_event_id(chat_id: ChatID, event: &CalendarEvent, now: i64) -> String {
+    let mut hasher = DefaultHasher::new();
+    chat_id.hash(&mut hasher);
+    event.get_summary().hash(&mut hasher);
+    format!("bb{:016x}{:x}", hasher.finish(), now)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Outbox {
+    events: Vec<PendingEvent>,
+}
+
+impl Outbox {
+    pub fn new() -> Self {
+        Self { events: Vec::new() }
+    }
+
+    pub fn push(&mut self, event: PendingEvent) {
+        let is_duplicate = event.get_key().is_some() &&
+                           self.events.iter().any(|pending| pending.get_key() == event.get_key());
+        if !is_duplicate {
+            self.events.push(event);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Removes and returns events which should be retried at `now`.
+    pub fn take_due(&mut self, now: i64) -> Vec<PendingEvent> {
+        let (due, waiting) = self.events
+            .drain(..)
+            .partition(|event| event.next_attempt <= now);
+        self.events = waiting;
+        due
+    }
+
+    /// Puts the event back after one more failed attempt, returns it back if attempts are exhausted.
+    pub fn retry_later(&mut self, mut event: PendingEvent, now: i64) -> Option<PendingEvent> {
+        if event.attempts >= MAX_ATTEMPTS {
+            return Some(event);
+        }
+
+        event.attempts += 1;
+        event.schedule_next_attempt(now);
+        self.push(event);
+        None
+    }
+
+    /// Drops repeated entries, e.g. left after a restart in the middle of saving.
+    pub fn deduplicate(&mut self) {
+        let mut keys = HashSet::new();
+        self.events.retain(|event| match event.get_key() {
+            Some(key) => keys.insert(key.clone()),
+            None => true,
+        });
+    }
+}
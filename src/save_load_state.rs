@@ -1,5 +1,8 @@
 use super::user_data::{ChatID, UserInfo};
 use super::message_processor::DialogsProcessor;
+use super::outbox::Outbox;
+
+use chrono::NaiveDate;
 
 use serde_json;
 
@@ -25,13 +28,24 @@ impl UserSerializationInfo {
 pub struct UserCollectionSerializationData {
     pub last_id: i64,
     pub users: Vec<UserSerializationInfo>,
+    #[serde(default)]
+    pub outbox: Outbox,
+    /// Date of the last posted daily digest
+    #[serde(default)]
+    pub last_digest: Option<NaiveDate>,
 }
 
 impl UserCollectionSerializationData {
-    pub fn new(last_id: i64, user_infos: Vec<UserSerializationInfo>) -> Self {
+    pub fn new(last_id: i64,
+               user_infos: Vec<UserSerializationInfo>,
+               outbox: Outbox,
+               last_digest: Option<NaiveDate>)
+               -> Self {
         Self {
             last_id: last_id,
             users: user_infos,
+            outbox,
+            last_digest,
         }
     }
 }
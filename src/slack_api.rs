@@ -0,0 +1,841 @@
+use super::basic_structures::{MessageSender, MessageProcessor, Menu, MenuId, IncomingUpdate, IncomingKind,
+                              SenderIdentity, make_callback_data, parse_callback_data};
+use super::configuration::SlackSettings;
+use super::user_data::{ChatID, MessageID};
+
+use chrono::Utc;
+
+use hyper;
+use hyper::header::{Authorization, Bearer, Connection, ContentType};
+use hyper::method::Method;
+use hyper::server::{Handler, Request, Response, Server};
+use hyper::status::StatusCode;
+use hyper_rustls;
+use ring::{digest, hmac};
+use serde_json;
+use serde_json::Value;
+use url::form_urlencoded;
+
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
+use std::sync::Mutex;
+use std::sync::mpsc::{channel, Sender, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+const TIMER_PERIOD_SECONDS: u64 = 30;
+const SIGNATURE_HEADER: &str = "X-Slack-Signature";
+const TIMESTAMP_HEADER: &str = "X-Slack-Request-Timestamp";
+const SIGNATURE_VERSION: &str = "v0";
+/// Older requests are rejected, so that recorded ones can't be replayed
+const MAX_REQUEST_AGE_SECONDS: i64 = 5 * 60;
+/// Slack user ids are base 36 numbers, longer ones don't fit a chat id
+const MAX_USER_ID_LENGTH: usize = 12;
+const MESSAGE_TS_FRACTION: i64 = 1_000_000;
+const USER_ID_DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+/// Slack requests are much smaller, longer ones are rejected
+const MAX_BODY_BYTES: u64 = 1024 * 1024;
+/// Ids of the last processed requests, retries come within minutes
+const MAX_RECENT_DELIVERIES: usize = 1000;
+
+/// User id like "U024BE7LH" as a chat id, replies are sent to direct messages with the user.
+fn user_to_chat(user_id: &str) -> Option<ChatID> {
+    if user_id.is_empty() || user_id.len() > MAX_USER_ID_LENGTH ||
+       !user_id.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) {
+        return None;
+    }
+    i64::from_str_radix(user_id, 36).ok().map(ChatID::new)
+}
+
+fn chat_to_user(chat_id: ChatID) -> String {
+    let mut id = chat_id.get();
+    let mut digits = Vec::new();
+    while id > 0 {
+        let base = USER_ID_DIGITS.len() as i64;
+        digits.push(USER_ID_DIGITS[(id % base) as usize] as char);
+        id /= base;
+    }
+    digits.iter().rev().collect()
+}
+
+/// Message timestamp like "1503435956.000247" as a message id.
+fn ts_to_message(ts: &str) -> Option<MessageID> {
+    let mut parts = ts.splitn(2, '.');
+    let seconds = parts.next().and_then(|seconds| seconds.parse::<i64>().ok());
+    let fraction = parts.next().filter(|fraction| fraction.len() == 6).and_then(|fraction| fraction.parse::<i64>().ok());
+    match (seconds, fraction) {
+        (Some(seconds), Some(fraction)) => Some(MessageID::new(seconds * MESSAGE_TS_FRACTION + fraction)),
+        _ => None,
+    }
+}
+
+fn message_to_ts(message_id: MessageID) -> String {
+    format!("{}.{:06}", message_id.get() / MESSAGE_TS_FRACTION, message_id.get() % MESSAGE_TS_FRACTION)
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return None;
+    }
+    text.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+                 let high = (pair[0] as char).to_digit(16)?;
+                 let low = (pair[1] as char).to_digit(16)?;
+                 Some((high * 16 + low) as u8)
+             })
+        .collect()
+}
+
+/// Slack signs "v0:<timestamp>:<body>" with the signing secret and sends "v0=<hex>".
+fn verify_signature(signing_secret: &str,
+                    timestamp: Option<&str>,
+                    signature: Option<&str>,
+                    body: &str,
+                    now: i64)
+                    -> bool {
+    let (timestamp, signature) = match (timestamp, signature) {
+        (Some(timestamp), Some(signature)) => (timestamp, signature),
+        _ => return false,
+    };
+    let is_recent = timestamp.parse::<i64>().map_or(false, |time| (now - time).abs() <= MAX_REQUEST_AGE_SECONDS);
+    let prefix = format!("{}=", SIGNATURE_VERSION);
+    let signature = match decode_hex(if signature.starts_with(&prefix) { &signature[prefix.len()..] } else { "" }) {
+        Some(ref signature) if !signature.is_empty() => signature.clone(),
+        _ => return false,
+    };
+
+    let key = hmac::VerificationKey::new(&digest::SHA256, signing_secret.as_bytes());
+    let data = format!("{}:{}:{}", SIGNATURE_VERSION, timestamp, body);
+    is_recent && hmac::verify(&key, data.as_bytes(), &signature).is_ok()
+}
+
+/// Update received by the HTTP server, the sender's name is looked up by the main loop.
+#[derive(Debug, Clone, PartialEq)]
+struct SlackUpdate {
+    user_id: String,
+    /// Direct message channel with the user if the update came from it
+    dm_channel: Option<String>,
+    /// Event id or trigger id, the same for retries of a request
+    delivery_id: Option<String>,
+    kind: IncomingKind,
+}
+
+#[derive(Debug, PartialEq)]
+enum SlackRequest {
+    /// Verification of the events URL
+    Challenge(String),
+    Update(SlackUpdate),
+    Ignored,
+}
+
+#[derive(Deserialize)]
+struct EventEnvelope {
+    #[serde(rename = "type")]
+    kind: String,
+    challenge: Option<String>,
+    event_id: Option<String>,
+    event: Option<MessageEvent>,
+}
+
+#[derive(Deserialize)]
+struct MessageEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    channel_type: Option<String>,
+    channel: Option<String>,
+    user: Option<String>,
+    text: Option<String>,
+    /// Set for messages of bots including own replies
+    bot_id: Option<String>,
+    /// Set for edits and other service messages
+    subtype: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct IdObject {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct InteractionMessage {
+    ts: String,
+}
+
+#[derive(Deserialize)]
+struct InteractionAction {
+    value: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct InteractionPayload {
+    #[serde(rename = "type")]
+    kind: String,
+    user: IdObject,
+    trigger_id: Option<String>,
+    channel: Option<IdObject>,
+    message: Option<InteractionMessage>,
+    #[serde(default)]
+    actions: Vec<InteractionAction>,
+}
+
+/// Events are posted as JSON, slash commands and button presses as forms.
+fn parse_request(body: &str) -> Result<SlackRequest, String> {
+    if body.starts_with('{') {
+        return parse_event(body);
+    }
+
+    let fields: HashMap<String, String> = form_urlencoded::parse(body.as_bytes()).into_owned().collect();
+    if let Some(payload) = fields.get("payload") {
+        return parse_interaction(payload);
+    }
+    match (fields.get("command"), fields.get("user_id")) {
+        (Some(command), Some(user_id)) => {
+            Ok(SlackRequest::Update(SlackUpdate {
+                                        user_id: user_id.clone(),
+                                        dm_channel: None,
+                                        delivery_id: fields.get("trigger_id").cloned(),
+                                        kind: IncomingKind::Command {
+                                            command: command.clone(),
+                                            arguments: fields.get("text").map_or("", |text| text.trim()).to_string(),
+                                        },
+                                    }))
+        }
+        _ => Err("neither a slash command nor an interaction".to_string()),
+    }
+}
+
+/// Only direct messages from users are processed.
+fn parse_event(body: &str) -> Result<SlackRequest, String> {
+    let envelope: EventEnvelope = serde_json::from_str(body).map_err(|error| error.to_string())?;
+    let delivery_id = envelope.event_id;
+    match (envelope.kind.as_str(), envelope.challenge, envelope.event) {
+        ("url_verification", Some(challenge), _) => Ok(SlackRequest::Challenge(challenge)),
+        ("event_callback", _, Some(event)) => {
+            let is_user_message = event.kind == "message" &&
+                                  event.channel_type.as_ref().map(String::as_str) == Some("im") &&
+                                  event.bot_id.is_none() && event.subtype.is_none();
+            match (event.user, event.text) {
+                (Some(user_id), Some(text)) if is_user_message => {
+                    Ok(SlackRequest::Update(SlackUpdate {
+                                                user_id,
+                                                dm_channel: event.channel,
+                                                delivery_id,
+                                                kind: IncomingKind::Text(text),
+                                            }))
+                }
+                _ => Ok(SlackRequest::Ignored),
+            }
+        }
+        _ => Ok(SlackRequest::Ignored),
+    }
+}
+
+fn parse_interaction(payload: &str) -> Result<SlackRequest, String> {
+    let payload: InteractionPayload = serde_json::from_str(payload).map_err(|error| error.to_string())?;
+    let value = payload.actions.into_iter().filter_map(|action| action.value).next();
+    match (payload.kind.as_str(), payload.message, value) {
+        ("block_actions", Some(message), Some(value)) => {
+            let message_id = ts_to_message(&message.ts)
+                .ok_or_else(|| format!("invalid message ts \"{}\"", message.ts))?;
+            let (menu_id, key) = parse_callback_data(&value);
+            Ok(SlackRequest::Update(SlackUpdate {
+                                        user_id: payload.user.id,
+                                        dm_channel: payload.channel.map(|channel| channel.id),
+                                        delivery_id: payload.trigger_id,
+                                        kind: IncomingKind::ButtonPress {
+                                            message_id,
+                                            menu_id,
+                                            key: key.to_string(),
+                                        },
+                                    }))
+        }
+        _ => Ok(SlackRequest::Ignored),
+    }
+}
+
+fn get_header(request: &Request, name: &str) -> Option<String> {
+    request.headers
+        .get_raw(name)
+        .and_then(|values| values.first())
+        .map(|value| String::from_utf8_lossy(value).into_owned())
+}
+
+/// Reads at most `MAX_BODY_BYTES` of the request.
+fn read_body<R: Read>(request: R) -> Result<String, StatusCode> {
+    let mut body = String::new();
+    request.take(MAX_BODY_BYTES + 1).read_to_string(&mut body).map_err(|_| StatusCode::BadRequest)?;
+    if body.len() as u64 > MAX_BODY_BYTES {
+        return Err(StatusCode::PayloadTooLarge);
+    }
+    Ok(body)
+}
+
+/// Remembers the id, returns false if it was already seen. Requests without ids are always new.
+fn is_new_delivery(recent_deliveries: &mut VecDeque<String>, delivery_id: Option<&String>) -> bool {
+    let delivery_id = match delivery_id {
+        Some(delivery_id) => delivery_id,
+        None => return true,
+    };
+    if recent_deliveries.contains(delivery_id) {
+        return false;
+    }
+    if recent_deliveries.len() >= MAX_RECENT_DELIVERIES {
+        recent_deliveries.pop_front();
+    }
+    recent_deliveries.push_back(delivery_id.clone());
+    true
+}
+
+/// Acknowledges requests right away, Slack expects a reply in 3 seconds.
+struct SlackHandler {
+    signing_secret: String,
+    updates: Mutex<Sender<SlackUpdate>>,
+    /// Slack retries requests which weren't acknowledged in time, even if they were received
+    recent_deliveries: Mutex<VecDeque<String>>,
+}
+
+impl SlackHandler {
+    fn is_new_delivery(&self, update: &SlackUpdate) -> bool {
+        match self.recent_deliveries.lock() {
+            Ok(mut recent_deliveries) => is_new_delivery(&mut recent_deliveries, update.delivery_id.as_ref()),
+            // better to process a retry twice than to lose an update
+            Err(_) => true,
+        }
+    }
+}
+
+impl Handler for SlackHandler {
+    fn handle(&self, mut request: Request, mut response: Response) {
+        let timestamp = get_header(&request, TIMESTAMP_HEADER);
+        let signature = get_header(&request, SIGNATURE_HEADER);
+        let body = if request.method != Method::Post {
+            Err(StatusCode::MethodNotAllowed)
+        } else {
+            read_body(&mut request)
+        };
+        let result = body.and_then(|body| {
+            if !verify_signature(&self.signing_secret,
+                                 timestamp.as_ref().map(String::as_str),
+                                 signature.as_ref().map(String::as_str),
+                                 &body,
+                                 Utc::now().timestamp()) {
+                return Err(StatusCode::Unauthorized);
+            }
+            parse_request(&body).map_err(|error| {
+                                             warn!("Couldn't parse Slack request: {}", error);
+                                             StatusCode::BadRequest
+                                         })
+        });
+
+        let reply = match result {
+            Ok(SlackRequest::Challenge(challenge)) => challenge,
+            Ok(SlackRequest::Update(ref update)) if !self.is_new_delivery(update) => {
+                debug!("Skipped retry of {:?}", update.delivery_id);
+                String::new()
+            }
+            Ok(SlackRequest::Update(update)) => {
+                let is_sent = self.updates.lock().ok().map_or(false, |updates| updates.send(update).is_ok());
+                if !is_sent {
+                    *response.status_mut() = StatusCode::ServiceUnavailable;
+                }
+                String::new()
+            }
+            Ok(SlackRequest::Ignored) => String::new(),
+            Err(status) => {
+                *response.status_mut() = status;
+                // the rest of the body mustn't be taken for the next request
+                response.headers_mut().set(Connection::close());
+                String::new()
+            }
+        };
+        if let Err(error) = response.send(reply.as_bytes()) {
+            warn!("Couldn't reply to Slack request: {}", error);
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TextObject {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    text: String,
+}
+
+impl TextObject {
+    fn plain(text: &str) -> Self {
+        Self {
+            kind: "plain_text",
+            text: text.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Button {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    text: TextObject,
+    value: String,
+    /// Has to be unique in the message
+    action_id: String,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Block {
+    Section { text: TextObject },
+    Actions { elements: Vec<Button> },
+}
+
+/// Text and a row of buttons for each menu row.
+fn make_blocks(text: &str, menu: Option<&Menu>, menu_id: MenuId) -> Result<String, String> {
+    let mut blocks = vec![Block::Section { text: TextObject::plain(text) }];
+    for (row_index, row) in menu.iter().flat_map(|menu| menu.iter()).enumerate() {
+        let elements = row.iter()
+            .enumerate()
+            .map(|(index, key)| {
+                     Button {
+                         kind: "button",
+                         text: TextObject::plain(key),
+                         value: make_callback_data(menu_id, key),
+                         action_id: format!("key_{}_{}", row_index, index),
+                     }
+                 })
+            .collect();
+        blocks.push(Block::Actions { elements });
+    }
+    serde_json::to_string(&blocks).map_err(|error| error.to_string())
+}
+
+/// Slack Web API, the URL points to a local mock in tests.
+struct SlackWebApi {
+    api_url: String,
+    bot_token: String,
+    client: hyper::Client,
+}
+
+impl SlackWebApi {
+    fn new(api_url: &str, bot_token: &str) -> Self {
+        let connector = hyper::net::HttpsConnector::new(hyper_rustls::TlsClient::new());
+        Self {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            bot_token: bot_token.to_string(),
+            client: hyper::Client::with_connector(connector),
+        }
+    }
+
+    /// Not all methods accept JSON, so parameters are sent as a form.
+    fn call(&self, method: &str, parameters: &[(&str, &str)]) -> Result<Value, String> {
+        let body = form_urlencoded::Serializer::new(String::new()).extend_pairs(parameters.iter()).finish();
+        let mut response = self.client
+            .post(&format!("{}/{}", self.api_url, method))
+            .header(ContentType::form_url_encoded())
+            .header(Authorization(Bearer { token: self.bot_token.clone() }))
+            .body(body.as_str())
+            .send()
+            .map_err(|error| error.to_string())?;
+        let mut reply = String::new();
+        response.read_to_string(&mut reply).map_err(|error| error.to_string())?;
+        let reply: Value = serde_json::from_str(&reply).map_err(|error| format!("{} replied {}", method, error))?;
+        if reply["ok"].as_bool() == Some(true) {
+            Ok(reply)
+        } else {
+            Err(format!("{} failed: {}", method, reply["error"].as_str().unwrap_or("unknown error")))
+        }
+    }
+}
+
+struct SlackMessageSender {
+    web_api: SlackWebApi,
+    status_channel: String,
+    /// Direct message channels by user id
+    dm_channels: HashMap<String, String>,
+}
+
+impl SlackMessageSender {
+    fn get_dm_channel(&mut self, chat_id: ChatID) -> Result<String, String> {
+        let user_id = chat_to_user(chat_id);
+        if let Some(channel) = self.dm_channels.get(&user_id) {
+            return Ok(channel.clone());
+        }
+        let reply = self.web_api.call("conversations.open", &[("users", &user_id)])?;
+        let channel = reply["channel"]["id"]
+            .as_str()
+            .ok_or_else(|| "conversations.open replied no channel".to_string())?
+            .to_string();
+        self.dm_channels.insert(user_id, channel.clone());
+        Ok(channel)
+    }
+
+    fn post_message(&mut self, chat_id: ChatID, text: &str, blocks: String) -> Result<(), String> {
+        let channel = self.get_dm_channel(chat_id)?;
+        self.web_api
+            .call("chat.postMessage", &[("channel", &channel), ("text", text), ("blocks", &blocks)])
+            .map(|_| ())
+    }
+
+    fn update_message(&mut self, chat_id: ChatID, message_id: MessageID, text: &str, blocks: String)
+                      -> Result<(), String> {
+        let channel = self.get_dm_channel(chat_id)?;
+        self.web_api
+            .call("chat.update",
+                  &[("channel", &channel), ("ts", &message_to_ts(message_id)), ("text", text), ("blocks", &blocks)])
+            .map(|_| ())
+    }
+}
+
+impl MessageSender for SlackMessageSender {
+    fn send_text(&mut self, chat_id: ChatID, text: String) {
+        let result = make_blocks(&text, None, 0).and_then(|blocks| self.post_message(chat_id, &text, blocks));
+        if let Err(error) = result {
+            warn!("Couldn't send message to {}: {}", chat_to_user(chat_id), error);
+        }
+    }
+
+    fn send_menu(&mut self, chat_id: ChatID, text: String, menu: Menu, menu_id: MenuId) {
+        let result = make_blocks(&text, Some(&menu), menu_id).and_then(|blocks| self.post_message(chat_id, &text, blocks));
+        if let Err(error) = result {
+            warn!("Couldn't send menu to {}: {}", chat_to_user(chat_id), error);
+        }
+    }
+
+    fn edit_message(&mut self, chat_id: ChatID, message_id: MessageID, text: String, menu: Option<Menu>, menu_id: MenuId) {
+        let result = make_blocks(&text, menu.as_ref(), menu_id)
+            .and_then(|blocks| self.update_message(chat_id, message_id, &text, blocks));
+        if let Err(error) = result {
+            warn!("Couldn't edit message of {}: {}", chat_to_user(chat_id), error);
+        }
+    }
+
+    /// Everything is posted to the configured Slack channel, Telegram channels of teams are ignored.
+    fn send_status_to_channel(&mut self, _channel: &str, text: String) {
+        let channel = self.status_channel.clone();
+        let result = make_blocks(&text, None, 0).and_then(|blocks| {
+            self.web_api.call("chat.postMessage", &[("channel", &channel), ("text", &text), ("blocks", &blocks)])
+        });
+        if let Err(error) = result {
+            warn!("Couldn't post to {}: {}", channel, error);
+        }
+    }
+}
+
+/// First and last name from the profile, Slack users don't have to fill them.
+fn to_sender(user: &Value, user_id: &str) -> SenderIdentity {
+    let get = |value: &Value| value.as_str().filter(|text| !text.is_empty()).map(str::to_string);
+    let profile = &user["profile"];
+    match get(&profile["first_name"]) {
+        Some(first_name) => SenderIdentity::new(&first_name, get(&profile["last_name"]).as_ref().map(String::as_str)),
+        None => {
+            let name = get(&profile["real_name"]).or_else(|| get(&user["name"])).unwrap_or_else(|| user_id.to_string());
+            SenderIdentity::new(&name, None)
+        }
+    }
+}
+
+pub struct SlackApi {
+    listen_address: String,
+    signing_secret: String,
+    message_sender: SlackMessageSender,
+    /// Slack updates have only user ids
+    senders: HashMap<String, SenderIdentity>,
+}
+
+impl SlackApi {
+    pub fn new(settings: &SlackSettings) -> Self {
+        Self {
+            listen_address: settings.listen_address.clone(),
+            signing_secret: settings.signing_secret.clone(),
+            message_sender: SlackMessageSender {
+                web_api: SlackWebApi::new(&settings.api_url, &settings.bot_token),
+                status_channel: settings.status_channel.clone(),
+                dm_channels: HashMap::new(),
+            },
+            senders: HashMap::new(),
+        }
+    }
+
+    /// Runs the HTTP server receiving slash commands, button presses and direct messages.
+    pub fn process_messages(&mut self, message_processor: &mut MessageProcessor) -> Result<(), String> {
+        let (sender, receiver) = channel();
+        let handler = SlackHandler {
+            signing_secret: self.signing_secret.clone(),
+            updates: Mutex::new(sender),
+            recent_deliveries: Mutex::new(VecDeque::new()),
+        };
+        let server = Server::http(self.listen_address.as_str()).map_err(|error| error.to_string())?;
+        let mut server = server.handle(handler).map_err(|error| error.to_string())?;
+        info!("Listening for Slack requests on {}", self.listen_address);
+
+        let timer_period = Duration::from_secs(TIMER_PERIOD_SECONDS);
+        let mut last_timer = Instant::now();
+        loop {
+            match receiver.recv_timeout(timer_period) {
+                Ok(update) => self.process_update(message_processor, update),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if last_timer.elapsed() >= timer_period {
+                message_processor.process_timer(&mut self.message_sender);
+                last_timer = Instant::now();
+            }
+        }
+
+        // Dropping the server would wait for its thread forever
+        if let Err(error) = server.close() {
+            error!("Couldn't stop Slack server: {}", error);
+        }
+        Err("Slack server stopped".to_string())
+    }
+
+    fn process_update(&mut self, message_processor: &mut MessageProcessor, update: SlackUpdate) {
+        let chat_id = match user_to_chat(&update.user_id) {
+            Some(chat_id) => chat_id,
+            None => {
+                warn!("Unsupported Slack user id {}", update.user_id);
+                return;
+            }
+        };
+        if let Some(channel) = update.dm_channel {
+            self.message_sender.dm_channels.insert(update.user_id.clone(), channel);
+        }
+
+        let incoming_update = IncomingUpdate {
+            chat_id,
+            sender: self.get_sender(&update.user_id),
+            kind: update.kind,
+        };
+        debug!("<{}>: {:?}", incoming_update.sender.first_name, incoming_update.kind);
+        if let Err(error) = message_processor.process_update(&mut self.message_sender, incoming_update) {
+            // Slack has no popup answers to button presses
+            self.message_sender.send_text(chat_id, error);
+        }
+    }
+
+    fn get_sender(&mut self, user_id: &str) -> SenderIdentity {
+        if let Some(sender) = self.senders.get(user_id) {
+            return sender.clone();
+        }
+        match self.message_sender.web_api.call("users.info", &[("user", user_id)]) {
+            Ok(reply) => {
+                let sender = to_sender(&reply["user"], user_id);
+                self.senders.insert(user_id.to_string(), sender.clone());
+                sender
+            }
+            Err(error) => {
+                warn!("Couldn't get name of {}: {}", user_id, error);
+                SenderIdentity::new(user_id, None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::uri::RequestUri;
+
+    use std::sync::Arc;
+
+    const SIGNING_SECRET: &str = "8f742231b10e8888abcd99yyyzzz85a5";
+    const SLASH_COMMAND: &str = "token=gIkuvaNzQIHg97ATvDxqgjtO&team_id=T0001&channel_id=C2147483705&\
+                                 user_id=U2147483697&user_name=steve&command=%2Fwfh&text=24.12-28.12+&\
+                                 response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1234%2F5678&\
+                                 trigger_id=13345224609.738474920.8088930838d88f008e0";
+    const MESSAGE_EVENT: &str = r#"{"type": "event_callback", "event_id": "Ev0PV52K21",
+                                    "event": {"type": "message", "channel_type": "im", "channel": "D0123456789",
+                                              "user": "U2147483697", "text": "tomorrow"}}"#;
+
+    fn sign(body: &str, timestamp: i64) -> String {
+        let key = hmac::SigningKey::new(&digest::SHA256, SIGNING_SECRET.as_bytes());
+        let data = format!("v0:{}:{}", timestamp, body);
+        let hex: String = hmac::sign(&key, data.as_bytes()).as_ref().iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("v0={}", hex)
+    }
+
+    #[test]
+    fn test_ids() {
+        let chat_id = user_to_chat("U024BE7LH").unwrap();
+        assert_eq!(chat_to_user(chat_id), "U024BE7LH");
+        assert_eq!(user_to_chat("u024be7lh"), None);
+        assert_eq!(user_to_chat("U024BE7LH0123"), None);
+
+        let message_id = ts_to_message("1503435956.000247").unwrap();
+        assert_eq!(message_to_ts(message_id), "1503435956.000247");
+        assert_eq!(ts_to_message("1503435956"), None);
+    }
+
+    #[test]
+    fn test_signature() {
+        let now = 1531420618;
+        let signature = sign(SLASH_COMMAND, now);
+        let timestamp = now.to_string();
+        let verify = |timestamp: &str, signature: &str, body: &str, now: i64| {
+            verify_signature(SIGNING_SECRET, Some(timestamp), Some(signature), body, now)
+        };
+
+        assert!(verify(&timestamp, &signature, SLASH_COMMAND, now + 10));
+        assert!(!verify(&timestamp, &signature, SLASH_COMMAND, now + MAX_REQUEST_AGE_SECONDS + 1));
+        assert!(!verify(&timestamp, &signature, "command=%2Fvacation", now));
+        assert!(!verify(&timestamp, &signature[3..], SLASH_COMMAND, now));
+        assert!(!verify(&timestamp, "v0=", SLASH_COMMAND, now));
+        assert!(!verify_signature(SIGNING_SECRET, None, Some(&signature), SLASH_COMMAND, now));
+    }
+
+    #[test]
+    fn test_parse_request() {
+        let update = |user_id: &str, dm_channel: Option<&str>, delivery_id: &str, kind: IncomingKind| {
+            Ok(SlackRequest::Update(SlackUpdate {
+                                        user_id: user_id.to_string(),
+                                        dm_channel: dm_channel.map(str::to_string),
+                                        delivery_id: Some(delivery_id.to_string()),
+                                        kind,
+                                    }))
+        };
+
+        assert_eq!(parse_request(SLASH_COMMAND),
+                   update("U2147483697",
+                          None,
+                          "13345224609.738474920.8088930838d88f008e0",
+                          IncomingKind::Command {
+                              command: "/wfh".to_string(),
+                              arguments: "24.12-28.12".to_string(),
+                          }));
+
+        let payload = r#"{"type": "block_actions", "user": {"id": "U2147483697", "name": "steve"}, "trigger_id": "T1",
+                          "channel": {"id": "D0123456789"}, "message": {"ts": "1503435956.000247"},
+                          "actions": [{"action_id": "key_0_0", "value": "3:yes"}]}"#;
+        let body = form_urlencoded::Serializer::new(String::new()).append_pair("payload", payload).finish();
+        assert_eq!(parse_request(&body),
+                   update("U2147483697",
+                          Some("D0123456789"),
+                          "T1",
+                          IncomingKind::ButtonPress {
+                              message_id: MessageID::new(1503435956000247),
+                              menu_id: Some(3),
+                              key: "yes".to_string(),
+                          }));
+
+        assert_eq!(parse_request(MESSAGE_EVENT),
+                   update("U2147483697",
+                          Some("D0123456789"),
+                          "Ev0PV52K21",
+                          IncomingKind::Text("tomorrow".to_string())));
+        let own_message = r#"{"type": "event_callback", "event": {"type": "message", "channel_type": "im",
+                              "channel": "D0123456789", "bot_id": "B01", "text": "Done"}}"#;
+        assert_eq!(parse_request(own_message), Ok(SlackRequest::Ignored));
+        assert_eq!(parse_request(r#"{"type": "url_verification", "challenge": "3eZbrw1aBm2rZgRNFdxV2595E9CY3gmdALWMmHkvFXO7tYXAYM8P"}"#),
+                   Ok(SlackRequest::Challenge("3eZbrw1aBm2rZgRNFdxV2595E9CY3gmdALWMmHkvFXO7tYXAYM8P".to_string())));
+        assert!(parse_request("token=123").is_err());
+    }
+
+    #[test]
+    fn test_events_server() {
+        let (sender, receiver) = channel();
+        let handler = SlackHandler {
+            signing_secret: SIGNING_SECRET.to_string(),
+            updates: Mutex::new(sender),
+            recent_deliveries: Mutex::new(VecDeque::new()),
+        };
+        let mut server = Server::http("127.0.0.1:0").unwrap().handle(handler).unwrap();
+        let url = format!("http://{}/", server.socket);
+
+        let client = hyper::Client::new();
+        let post = |body: &str, signature: &str| {
+            let mut headers = hyper::header::Headers::new();
+            headers.set_raw(TIMESTAMP_HEADER, vec![Utc::now().timestamp().to_string().into_bytes()]);
+            headers.set_raw(SIGNATURE_HEADER, vec![signature.as_bytes().to_vec()]);
+            let mut response = client.post(&url).headers(headers).body(body).send().unwrap();
+            let mut reply = String::new();
+            response.read_to_string(&mut reply).unwrap();
+            response.status
+        };
+
+        assert_eq!(post(SLASH_COMMAND, "v0=0123"), StatusCode::Unauthorized);
+        assert_eq!(post(SLASH_COMMAND, &sign(SLASH_COMMAND, Utc::now().timestamp())), StatusCode::Ok);
+        // a retry of an event which was received but not acknowledged in time
+        for _ in 0..2 {
+            assert_eq!(post(MESSAGE_EVENT, &sign(MESSAGE_EVENT, Utc::now().timestamp())), StatusCode::Ok);
+        }
+        server.close().unwrap();
+
+        let updates: Vec<_> = receiver.try_iter().map(|update| update.kind).collect();
+        assert_eq!(updates,
+                   vec![IncomingKind::Command {
+                            command: "/wfh".to_string(),
+                            arguments: "24.12-28.12".to_string(),
+                        },
+                        IncomingKind::Text("tomorrow".to_string())]);
+    }
+
+    #[test]
+    fn test_deliveries() {
+        let mut recent_deliveries = VecDeque::new();
+        assert!(is_new_delivery(&mut recent_deliveries, None));
+        assert!(is_new_delivery(&mut recent_deliveries, None));
+        for index in 0..MAX_RECENT_DELIVERIES {
+            assert!(is_new_delivery(&mut recent_deliveries, Some(&index.to_string())));
+        }
+        assert!(!is_new_delivery(&mut recent_deliveries, Some(&"1".to_string())));
+        assert!(is_new_delivery(&mut recent_deliveries, Some(&"new".to_string())));
+        // the oldest id is forgotten
+        assert!(is_new_delivery(&mut recent_deliveries, Some(&"0".to_string())));
+
+        assert_eq!(read_body(SLASH_COMMAND.as_bytes()), Ok(SLASH_COMMAND.to_string()));
+        assert_eq!(read_body(" ".repeat(MAX_BODY_BYTES as usize + 1).as_bytes()), Err(StatusCode::PayloadTooLarge));
+    }
+
+    #[test]
+    fn test_message_sender() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let mock = move |mut request: Request, response: Response| {
+            let method = match request.uri {
+                RequestUri::AbsolutePath(ref path) => path.trim_start_matches("/api/").to_string(),
+                _ => String::new(),
+            };
+            let mut body = String::new();
+            request.read_to_string(&mut body).unwrap();
+            let fields: HashMap<String, String> = form_urlencoded::parse(body.as_bytes()).into_owned().collect();
+            recorded.lock().unwrap().push((method.clone(), fields));
+
+            let reply = match method.as_str() {
+                "conversations.open" => r#"{"ok": true, "channel": {"id": "D0123456789"}}"#,
+                "chat.postMessage" | "chat.update" => r#"{"ok": true}"#,
+                _ => r#"{"ok": false, "error": "unknown_method"}"#,
+            };
+            response.send(reply.as_bytes()).unwrap();
+        };
+        let mut server = Server::http("127.0.0.1:0").unwrap().handle(mock).unwrap();
+
+        let mut message_sender = SlackMessageSender {
+            web_api: SlackWebApi::new(&format!("http://{}/api/", server.socket), "xoxb-token"),
+            status_channel: "#absences".to_string(),
+            dm_channels: HashMap::new(),
+        };
+        let chat_id = user_to_chat("U2147483697").unwrap();
+        let menu = vec![vec!["yes".to_string(), "no".to_string()]];
+        message_sender.send_menu(chat_id, "Confirm event wfh for today?".to_string(), menu, 3);
+        message_sender.edit_message(chat_id,
+                                    MessageID::new(1503435956000247),
+                                    "Posted wfh for today".to_string(),
+                                    None,
+                                    3);
+        message_sender.send_status_to_channel("@BigBrotherEvents", "John Doe: wfh today".to_string());
+        server.close().unwrap();
+
+        let requests = requests.lock().unwrap();
+        let methods: Vec<_> = requests.iter().map(|&(ref method, _)| method.as_str()).collect();
+        assert_eq!(methods, vec!["conversations.open", "chat.postMessage", "chat.update", "chat.postMessage"]);
+        assert_eq!(requests[0].1["users"], "U2147483697");
+
+        let blocks: Value = serde_json::from_str(&requests[1].1["blocks"]).unwrap();
+        assert_eq!(requests[1].1["channel"], "D0123456789");
+        assert_eq!(blocks[0]["text"]["text"], "Confirm event wfh for today?");
+        assert_eq!(blocks[1]["elements"][1]["value"], "3:no");
+
+        assert_eq!(requests[2].1["ts"], "1503435956.000247");
+        assert_eq!(serde_json::from_str::<Value>(&requests[2].1["blocks"]).unwrap().as_array().unwrap().len(), 1);
+        assert_eq!(requests[3].1["channel"], "#absences");
+    }
+}
@@ -1,16 +1,193 @@
-use super::basic_structures::{MessageSender, MessageProcessor};
-use super::user_data::ChatID;
+use super::basic_structures::{MessageSender, MessageProcessor, Menu, MenuId, IncomingUpdate, SenderIdentity,
+                              make_callback_data, parse_callback_data};
+use super::configuration::WebhookSettings;
+use super::message_processor::CommandDescription;
+use super::user_data::{ChatID, MessageID};
 
-use telegram_bot::{Api, ReplyMarkup, InlineKeyboardMarkup, UpdateKind, MessageKind, ChatRef, SendMessage,
-InlineKeyboardButton, User, CallbackQuery, CanAnswerCallbackQuery, CanEditMessageReplyMarkup};
+use ring::constant_time;
 
-use tokio_core::reactor::Core;
+use telegram_bot::{Api, Update, ReplyMarkup, InlineKeyboardMarkup, UpdateKind, MessageKind, ChatRef, SendMessage,
+InlineKeyboardButton, User, CallbackQuery, CanAnswerCallbackQuery, CanEditMessageReplyMarkup, EditMessageText,
+ChatId, MessageId};
+
+use tokio_core::reactor::{Core, Interval};
 
 use futures::Stream;
+use futures::sync::mpsc::{unbounded, UnboundedSender, UnboundedReceiver};
+
+use hyper;
+use hyper::header::Connection;
+use hyper::method::Method;
+use hyper::server::{Handler, Listening, Request, Response, Server};
+use hyper::status::StatusCode;
+use hyper_rustls;
+use serde::Serialize;
+use serde_json;
+
+use std::io::Read;
+use std::time::Duration;
+
+const TIMER_PERIOD_SECONDS: u64 = 30;
+const BOT_API_URL: &str = "https://api.telegram.org/bot";
+const SECRET_TOKEN_HEADER: &str = "X-Telegram-Bot-Api-Secret-Token";
+/// Updates are much smaller, longer webhook requests are rejected
+const MAX_BODY_BYTES: u64 = 1024 * 1024;
+
+#[derive(Serialize)]
+struct BotCommand {
+    /// Without the leading slash
+    command: String,
+    description: String,
+}
+
+#[derive(Serialize)]
+struct SetMyCommands {
+    commands: Vec<BotCommand>,
+}
+
+#[derive(Serialize)]
+struct SetWebhook {
+    url: String,
+    secret_token: String,
+}
+
+fn make_keyboard(menu: &Menu, menu_id: MenuId) -> InlineKeyboardMarkup {
+    let mut result = InlineKeyboardMarkup::new();
+    for row in menu.iter() {
+        let keys_row = row.iter()
+            .map(|ref key_name| InlineKeyboardButton::callback(key_name, make_callback_data(menu_id, key_name)))
+            .collect();
+        result.add_row(keys_row);
+    }
+    result
+}
+
+/// Telegram accepts 1-32 lowercase letters, digits and underscores.
+fn to_bot_command(description: &CommandDescription) -> Option<BotCommand> {
+    let command = &description.command;
+    let command = if command.starts_with('/') { &command[1..] } else { command.as_str() };
+    let is_valid = !command.is_empty() && command.len() <= 32 &&
+                   command.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if is_valid {
+        Some(BotCommand {
+                 command: command.to_string(),
+                 description: description.description.clone(),
+             })
+    } else {
+        None
+    }
+}
+
+fn to_chat(chat_id: ChatID) -> ChatRef {
+    ChatRef::from_chat_id(ChatId::new(chat_id.get()))
+}
+
+fn to_sender(user: &User) -> SenderIdentity {
+    SenderIdentity::new(&user.first_name, user.last_name.as_ref().map(String::as_str))
+}
+
+/// Text messages and button presses, other updates are ignored.
+fn to_incoming_update(update: &Update) -> Option<IncomingUpdate> {
+    match update.kind {
+        UpdateKind::Message(ref message) => {
+            match message.kind {
+                MessageKind::Text { ref data, .. } => {
+                    Some(IncomingUpdate::from_text(ChatID::new(message.chat.id().into()),
+                                                   to_sender(&message.from),
+                                                   data))
+                }
+                _ => None,
+            }
+        }
+        UpdateKind::CallbackQuery(ref query) => {
+            let (menu_id, key) = parse_callback_data(&query.data);
+            Some(IncomingUpdate::button_press(ChatID::new(query.message.chat.id().into()),
+                                              to_sender(&query.from),
+                                              MessageID::new(query.message.id.into()),
+                                              menu_id,
+                                              key))
+        }
+        _ => None,
+    }
+}
+
+/// Only requests with the configured secret token are accepted, the comparison takes constant time.
+fn check_secret_token(secret_token: Option<&[u8]>, expected_secret_token: &str) -> Result<(), StatusCode> {
+    match secret_token {
+        Some(secret_token) if constant_time::verify_slices_are_equal(secret_token,
+                                                                    expected_secret_token.as_bytes())
+                                  .is_ok() => Ok(()),
+        _ => Err(StatusCode::Unauthorized),
+    }
+}
+
+/// Reads at most `MAX_BODY_BYTES` of the request.
+fn read_body<R: Read>(request: R) -> Result<String, StatusCode> {
+    let mut body = String::new();
+    request.take(MAX_BODY_BYTES + 1).read_to_string(&mut body).map_err(|_| StatusCode::BadRequest)?;
+    if body.len() as u64 > MAX_BODY_BYTES {
+        return Err(StatusCode::PayloadTooLarge);
+    }
+    Ok(body)
+}
+
+/// Update posted to the webhook.
+fn parse_webhook_update(body: &str) -> Result<Update, StatusCode> {
+    serde_json::from_str(body).map_err(|error| {
+        warn!("Couldn't parse webhook update: {}", error);
+        StatusCode::BadRequest
+    })
+}
+
+struct WebhookHandler {
+    secret_token: String,
+    updates: UnboundedSender<Update>,
+}
+
+impl Handler for WebhookHandler {
+    fn handle(&self, mut request: Request, mut response: Response) {
+        // the body of unauthorized requests isn't read at all
+        let result = if request.method != Method::Post {
+            Err(StatusCode::MethodNotAllowed)
+        } else {
+            let secret_token = request.headers
+                .get_raw(SECRET_TOKEN_HEADER)
+                .and_then(|values| values.first())
+                .map(Vec::as_slice);
+            check_secret_token(secret_token, &self.secret_token)
+        };
+        let result = result.and_then(|_| read_body(&mut request)).and_then(|body| parse_webhook_update(&body));
+
+        match result {
+            Ok(update) => {
+                if self.updates.unbounded_send(update).is_err() {
+                    *response.status_mut() = StatusCode::ServiceUnavailable;
+                }
+            }
+            Err(status) => {
+                *response.status_mut() = status;
+                // the rest of the body mustn't be taken for the next request
+                response.headers_mut().set(Connection::close());
+            }
+        }
+        if let Err(error) = response.send(b"") {
+            warn!("Couldn't reply to webhook request: {}", error);
+        }
+    }
+}
+
+enum Incoming {
+    Update(Update),
+    Timer,
+}
 
 pub struct TelegramApi {
     core: Core,
     bot_api: Api,
+    bot_token: String,
+    webhook_server: Option<Listening>,
+    /// Updates are polled if the webhook isn't started
+    webhook_updates: Option<UnboundedReceiver<Update>>,
 }
 
 impl TelegramApi {
@@ -18,47 +195,108 @@ impl TelegramApi {
         let core = Core::new().unwrap();
         let bot_api = Api::configure(bot_token).build(core.handle()).unwrap();
 
-        return Self {core, bot_api};
+        return Self {
+            core,
+            bot_api,
+            bot_token: bot_token.to_string(),
+            webhook_server: None,
+            webhook_updates: None,
+        };
+    }
+
+    /// Makes clients suggest the commands, telegram-bot doesn't support `setMyCommands` yet.
+    pub fn set_commands(&self, commands: &[CommandDescription]) -> Result<(), String> {
+        let request = SetMyCommands { commands: commands.iter().filter_map(to_bot_command).collect() };
+        self.call_bot_api("setMyCommands", &request)
+    }
+
+    /// Starts the HTTP server receiving updates and registers it in Telegram if the public URL is known.
+    pub fn start_webhook(&mut self, settings: &WebhookSettings) -> Result<(), String> {
+        let (sender, receiver) = unbounded();
+        let handler = WebhookHandler {
+            secret_token: settings.secret_token.clone(),
+            updates: sender,
+        };
+        let server = Server::http(settings.listen_address.as_str()).map_err(|error| error.to_string())?;
+        self.webhook_server = Some(server.handle(handler).map_err(|error| error.to_string())?);
+        self.webhook_updates = Some(receiver);
+        info!("Listening for webhook updates on {}", settings.listen_address);
+
+        if let Some(ref url) = settings.url {
+            let request = SetWebhook {
+                url: url.clone(),
+                secret_token: settings.secret_token.clone(),
+            };
+            self.call_bot_api("setWebhook", &request)?;
+        }
+        Ok(())
+    }
+
+    /// Bot API methods not supported by telegram-bot.
+    fn call_bot_api<T: Serialize>(&self, method: &str, request: &T) -> Result<(), String> {
+        let body = serde_json::to_string(request).map_err(|error| error.to_string())?;
+
+        let connector = hyper::net::HttpsConnector::new(hyper_rustls::TlsClient::new());
+        let client = hyper::Client::with_connector(connector);
+        let response = client.post(&format!("{}{}/{}", BOT_API_URL, self.bot_token, method))
+            .header(hyper::header::ContentType::json())
+            .body(body.as_str())
+            .send()
+            .map_err(|error| error.to_string())?;
+        if response.status.is_success() {
+            Ok(())
+        } else {
+            Err(response.status.to_string())
+        }
     }
 
     pub fn process_messages(self: &mut Self, message_processor: &mut MessageProcessor) {
+        let updates: Box<Stream<Item = Update, Error = String>> = match self.webhook_updates.take() {
+            Some(receiver) => Box::new(receiver.map_err(|_| "webhook server stopped".to_string())),
+            None => Box::new(self.bot_api.stream().map_err(|error| format!("{:?}", error))),
+        };
+        let updates = updates.map(Incoming::Update);
         let mut message_sender = TelegramMessageSender::new(&self.bot_api);
 
-        let future = self.bot_api.stream().for_each(|update| {
-            fn get_last_name(user: &User) -> Option<&str> {
-                user.last_name.as_ref().map(|string| string.as_str())
-            }
+        let timer = Interval::new(Duration::from_secs(TIMER_PERIOD_SECONDS), &self.core.handle())
+            .unwrap()
+            .map(|_| Incoming::Timer)
+            .map_err(|error| format!("{:?}", error));
 
-            if let UpdateKind::Message(message) = update.kind {
-                if let MessageKind::Text {ref data, ..} = message.kind {
-                    if message_processor.is_new_message(update.id) {
-                        message_processor.process_message(& mut message_sender, 
-                                                          message.chat.id(),
-                                                          message.from.first_name.as_str(),
-                                                          get_last_name(&message.from),
-                                                          data);
-                    }
-                        
-                    //message_sender.send_status_to_channel(format!("<{}>: {}", &message.from.first_name, data));
-                     // Print received text message to stdout.
-                    println!("<{}>: {}", &message.from.first_name, data);
+        let future = updates.select(timer).for_each(|incoming| {
+            let update = match incoming {
+                Incoming::Update(update) => update,
+                Incoming::Timer => {
+                    message_processor.process_timer(&mut message_sender);
+                    return Ok(());
+                }
+            };
+
+            let incoming_update = match to_incoming_update(&update) {
+                Some(incoming_update) => incoming_update,
+                None => return Ok(()),
+            };
+            debug!("<{}>: {:?}", incoming_update.sender.first_name, incoming_update.kind);
+            if let UpdateKind::CallbackQuery(ref callback_query) = update.kind {
+                let result = message_processor.process_update(&mut message_sender, incoming_update);
+                message_sender.send_query_reply(callback_query, result.err());
+            } else if message_processor.is_new_message(update.id) {
+                if let Err(error) = message_processor.process_update(&mut message_sender, incoming_update) {
+                    warn!("Update {} is ignored: {}", update.id, error);
                 }
-            }
-            else if let UpdateKind::CallbackQuery(ref callback_query) = update.kind {
-                message_sender.send_query_reply(&callback_query);
-                message_processor.process_message(& mut message_sender, 
-                                                  callback_query.message.chat.id(),
-                                                  callback_query.from.first_name.as_str(),
-                                                  get_last_name(&callback_query.from),
-                                                  &callback_query.data);
-
-                println!("<{}>: {}", callback_query.from.first_name, callback_query.data);
             }
 
             Ok(())
         });
 
-        self.core.run(future).unwrap();
+        let result = self.core.run(future);
+        if let Some(mut server) = self.webhook_server.take() {
+            // Dropping the server would wait for its thread forever
+            if let Err(error) = server.close() {
+                error!("Couldn't stop webhook server: {}", error);
+            }
+        }
+        result.unwrap();
     }
 }
 
@@ -71,38 +309,136 @@ impl<'a> TelegramMessageSender<'a> {
         Self { bot_api}
     }
 
-    fn send_query_reply(self: &mut Self, query: &CallbackQuery) {
-        self.bot_api.spawn(query.message.edit_reply_markup::<ReplyMarkup>(Option::None));
-        self.bot_api.spawn(query.answer(""));
+    /// Accepted menus are edited by the dialog, rejected ones lose their buttons and the reason is shown as a toast.
+    fn send_query_reply(self: &mut Self, query: &CallbackQuery, rejection: Option<String>) {
+        match rejection {
+            Some(text) => {
+                self.bot_api.spawn(query.message.edit_reply_markup::<ReplyMarkup>(Option::None));
+                self.bot_api.spawn(query.answer(text));
+            }
+            None => self.bot_api.spawn(query.answer("")),
+        }
     }
 }
 
 impl<'a> MessageSender for TelegramMessageSender<'a> {
     fn send_text(&mut self, chat_id: ChatID, text: String) {
-        let chat = ChatRef::from_chat_id(chat_id);
-        let message_req = SendMessage::new(chat, text);
+        let message_req = SendMessage::new(to_chat(chat_id), text);
         self.bot_api.spawn(message_req);
     }
 
-    fn send_menu(&mut self, chat_id: ChatID, text: String, menu: Vec<Vec<String>>) {
-        let reply_markup = (|| -> InlineKeyboardMarkup {
-            let mut result = InlineKeyboardMarkup::new();
-            for row in menu.iter() {
-                let keys_row = row.iter().map(|ref key_name| InlineKeyboardButton::callback(key_name, key_name)).collect();
-                result.add_row(keys_row);
-            }
-            result
-        })();
-        let chat = ChatRef::from_chat_id(chat_id);
-        let mut message_req = SendMessage::new(chat, text);
-        message_req.reply_markup(ReplyMarkup::InlineKeyboardMarkup(reply_markup));
+    fn send_menu(&mut self, chat_id: ChatID, text: String, menu: Menu, menu_id: MenuId) {
+        let mut message_req = SendMessage::new(to_chat(chat_id), text);
+        message_req.reply_markup(ReplyMarkup::InlineKeyboardMarkup(make_keyboard(&menu, menu_id)));
+        self.bot_api.spawn(message_req);
+    }
+
+    fn edit_message(&mut self, chat_id: ChatID, message_id: MessageID, text: String, menu: Option<Menu>, menu_id: MenuId) {
+        let mut message_req = EditMessageText::new(to_chat(chat_id), MessageId::new(message_id.get()), text);
+        if let Some(menu) = menu {
+            message_req.reply_markup(ReplyMarkup::InlineKeyboardMarkup(make_keyboard(&menu, menu_id)));
+        }
         self.bot_api.spawn(message_req);
     }
 
-    fn send_status_to_channel(&mut self, text: String) {
-        // TODO: move to configuration
-        let chat = ChatRef::ChannelUsername("@BigBrotherEvents".to_owned());
+    fn send_status_to_channel(&mut self, channel: &str, text: String) {
+        let chat = match channel.parse::<i64>() {
+            Ok(id) => ChatRef::from_chat_id(ChatId::new(id)),
+            Err(_) => ChatRef::ChannelUsername(channel.to_owned()),
+        };
         let message_req = SendMessage::new(chat, text);
         self.bot_api.spawn(message_req);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::Future;
+
+    use std::io::Read;
+
+    const SECRET_TOKEN: &str = "test-secret";
+    /// Update recorded from Telegram
+    const TEXT_UPDATE: &str = r#"{
+        "update_id": 10000,
+        "message": {
+            "message_id": 1365,
+            "date": 1441645532,
+            "chat": {"id": 1111111, "type": "private", "first_name": "Test", "last_name": "Testov"},
+            "from": {"id": 1111111, "is_bot": false, "first_name": "Test", "last_name": "Testov"},
+            "text": "/wfh"
+        }
+    }"#;
+
+    #[test]
+    fn test_parse_webhook_update() {
+        let update = parse_webhook_update(TEXT_UPDATE).unwrap();
+        assert_eq!(update.id, 10000);
+        match update.kind {
+            UpdateKind::Message(message) => {
+                match message.kind {
+                    MessageKind::Text { ref data, .. } => assert_eq!(data, "/wfh"),
+                    ref other => panic!("Unexpected message {:?}", other),
+                }
+            }
+            other => panic!("Unexpected update {:?}", other),
+        }
+
+        assert_eq!(parse_webhook_update("{}").unwrap_err(), StatusCode::BadRequest);
+    }
+
+    #[test]
+    fn test_check_secret_token() {
+        assert_eq!(check_secret_token(Some(SECRET_TOKEN.as_bytes()), SECRET_TOKEN), Ok(()));
+        assert_eq!(check_secret_token(None, SECRET_TOKEN), Err(StatusCode::Unauthorized));
+        assert_eq!(check_secret_token(Some(b"wrong"), SECRET_TOKEN), Err(StatusCode::Unauthorized));
+        assert_eq!(check_secret_token(Some(b"test-secret-"), SECRET_TOKEN), Err(StatusCode::Unauthorized));
+    }
+
+    #[test]
+    fn test_read_body() {
+        assert_eq!(read_body(TEXT_UPDATE.as_bytes()), Ok(TEXT_UPDATE.to_string()));
+        let body = " ".repeat(MAX_BODY_BYTES as usize);
+        assert_eq!(read_body(body.as_bytes()), Ok(body.clone()));
+        assert_eq!(read_body((body + " ").as_bytes()), Err(StatusCode::PayloadTooLarge));
+    }
+
+    #[test]
+    fn test_incoming_update() {
+        let update = serde_json::from_str(TEXT_UPDATE).unwrap();
+        assert_eq!(to_incoming_update(&update),
+                   Some(IncomingUpdate::from_text(ChatID::new(1111111),
+                                                  SenderIdentity::new("Test", Some("Testov")),
+                                                  "/wfh")));
+    }
+
+    #[test]
+    fn test_webhook_server() {
+        let (sender, receiver) = unbounded();
+        let handler = WebhookHandler {
+            secret_token: SECRET_TOKEN.to_string(),
+            updates: sender,
+        };
+        let mut server = Server::http("127.0.0.1:0").unwrap().handle(handler).unwrap();
+        let url = format!("http://{}/", server.socket);
+
+        let client = hyper::Client::new();
+        let post = |secret_token: &str| {
+            let mut headers = hyper::header::Headers::new();
+            headers.set_raw(SECRET_TOKEN_HEADER, vec![secret_token.as_bytes().to_vec()]);
+            let mut response = client.post(&url).headers(headers).body(TEXT_UPDATE).send().unwrap();
+            let mut body = String::new();
+            response.read_to_string(&mut body).unwrap();
+            response.status
+        };
+
+        assert_eq!(post("wrong"), StatusCode::Unauthorized);
+        assert_eq!(post(SECRET_TOKEN), StatusCode::Ok);
+        server.close().unwrap();
+
+        let (update, _) = receiver.into_future().wait().ok().unwrap();
+        assert_eq!(update.unwrap().id, 10000);
+    }
+}
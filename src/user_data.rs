@@ -1,4 +1,42 @@
-pub type ChatID = super::telegram_bot::ChatId;
+use super::basic_structures::{CalendarEvent, UserTimeZone};
+
+use chrono::NaiveDate;
+
+use std::fmt;
+
+/// Chat with the user in the front-end, serialized as a plain number.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChatID(i64);
+
+impl ChatID {
+    pub fn new(id: i64) -> Self {
+        ChatID(id)
+    }
+
+    pub fn get(&self) -> i64 {
+        self.0
+    }
+}
+
+impl fmt::Display for ChatID {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.0)
+    }
+}
+
+/// Message sent to the user, needed to edit its menu.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MessageID(i64);
+
+impl MessageID {
+    pub fn new(id: i64) -> Self {
+        MessageID(id)
+    }
+
+    pub fn get(&self) -> i64 {
+        self.0
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UserName {
@@ -28,10 +66,59 @@ impl UserName {
     }
 }
 
+/// Event posted to the calendar on behalf of the user.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserEvent {
+    pub id: String,
+    /// Name of the absence kind, e.g. "wfh"
+    pub kind: String,
+    /// Team of the user when the event was posted, defines the calendar
+    #[serde(default)]
+    pub team: Option<String>,
+    pub event: CalendarEvent,
+}
+
+impl UserEvent {
+    pub fn new(id: String, kind: String, team: Option<String>, event: CalendarEvent) -> Self {
+        Self { id, kind, team, event }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReminderSettings {
+    /// Users are reminded unless they opt out
+    pub enabled: bool,
+    /// Overrides the configured reminder hour
+    pub hour: Option<u32>,
+    pub last_reminder: Option<NaiveDate>,
+}
+
+impl Default for ReminderSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            hour: None,
+            last_reminder: None,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UserInfo {
     pub chat_id: ChatID,
     pub name: UserName,
+    #[serde(default)]
+    pub events: Vec<UserEvent>,
+    #[serde(default)]
+    pub team: Option<String>,
+    #[serde(default)]
+    pub reminders: ReminderSettings,
+    /// Date of the last message from the user
+    #[serde(default)]
+    pub last_active: Option<NaiveDate>,
+    /// IANA name like "Europe/Berlin", the server zone is used if not set
+    #[serde(default)]
+    pub time_zone: Option<String>,
 }
 
 impl UserInfo {
@@ -39,6 +126,11 @@ impl UserInfo {
         Self {
             chat_id,
             name: UserName::new(first_name, last_name),
+            events: Vec::new(),
+            team: None,
+            reminders: ReminderSettings::default(),
+            last_active: None,
+            time_zone: None,
         }
     }
 
@@ -46,6 +138,13 @@ impl UserInfo {
         self.name.calendar_name.as_ref()
     }
 
+    pub fn get_time_zone(&self) -> UserTimeZone {
+        self.time_zone
+            .as_ref()
+            .and_then(|name| name.parse().ok())
+            .map_or(UserTimeZone::Server, UserTimeZone::Named)
+    }
+
     pub fn get_first_name(&self) -> &String {
         &self.name.first_name
     }
@@ -57,4 +156,18 @@ impl UserInfo {
     pub fn set_calendar_name(&mut self, calendar_name: String) {
         self.name.calendar_name = Some(calendar_name)
     }
+
+    pub fn add_event(&mut self, event: UserEvent) {
+        self.events.push(event);
+    }
+
+    pub fn remove_event(&mut self, event_id: &str) {
+        self.events.retain(|event| event.id != event_id);
+    }
+
+    pub fn update_event(&mut self, event_id: &str, new_event: CalendarEvent) {
+        for event in self.events.iter_mut().filter(|event| event.id == event_id) {
+            event.event = new_event.clone();
+        }
+    }
 }